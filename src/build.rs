@@ -2,7 +2,7 @@ use std::{
     env::consts::OS,
     fs::{self, File},
     io::Write,
-    path::Path,
+    path::{Path, PathBuf},
     process::{exit, Command},
 };
 
@@ -12,11 +12,29 @@ use crate::{
     codegen::codegen,
     grm,
     lex::{lex, Tokens},
+    manifest::Manifest,
     nulibc,
     p::parse,
 };
 
+/// Where a build puts its files and how it compiles them.
+struct BuildOpts {
+    /// The executable to produce; the generated C is written next to it as `<output>.c`.
+    output: PathBuf,
+    /// The directory `nulibc.c` and `nulibc.h` are written to.
+    dir: PathBuf,
+    opt_level: i32,
+    static_link: bool,
+    /// Extra C files to compile and link in.
+    c_sources: Vec<PathBuf>,
+    retain_c: bool,
+}
+
 pub fn build(args: &[String]) {
+    if args.len() < 3 {
+        eprintln!("{}", "Error :~ No source file/project given to build".red());
+        exit(-1);
+    }
     let src_path = Path::new(&args[2]);
 
     if !src_path.exists() {
@@ -41,42 +59,93 @@ pub fn build(args: &[String]) {
         exit(-1);
     }
 }
-#[allow(unused)]
-fn build_dir(args: &[String], src: &Path) {
-    println!("{}", "Building projects coming soon!".red());
-    exit(0);
-}
-
-fn build_file(args: &[String], src: &Path) {
-    println!("{} {}", "Building file :".green(), src.display());
+/// Builds the project in `dir` as described by its `neit.toml`, putting everything it
+/// produces in `dir/target`.
+fn build_dir(args: &[String], dir: &Path) {
+    let manifest_path = dir.join("neit.toml");
+    let text = fs::read_to_string(&manifest_path).unwrap_or_else(|e| {
+        eprintln!(
+            "{} {}",
+            "Error :~ Cannot read project manifest :".red(),
+            manifest_path.display()
+        );
+        eprintln!("{} {}", "Error MSG :~".red(), e.to_string().bright_red());
+        exit(-1);
+    });
+    let manifest = Manifest::parse(&text).unwrap_or_else(|e| {
+        eprintln!(
+            "{} {}",
+            format!("Error :~ Invalid {},", manifest_path.display()).red(),
+            e.bright_red()
+        );
+        exit(-1);
+    });
+    println!("{} {}", "Building project :".green(), manifest.name);
 
-    let mut code = fs::read_to_string(src).unwrap_or_else(|e| {
+    let src = dir.join(&manifest.entry);
+    if !src.is_file() {
         eprintln!(
             "{} {}",
-            "Error :~ Cannot read source file :".red(),
+            "Error :~ Cannot stat entry file :".red(),
             src.display()
         );
+        exit(-1);
+    }
+    let target = dir.join("target");
+    if let Err(e) = fs::create_dir_all(&target) {
+        eprintln!(
+            "{} {}",
+            "Error :~ Cannot create directory :".red(),
+            target.display()
+        );
         eprintln!("{} {}", "Error MSG :~".red(), e.to_string().bright_red());
         exit(-1);
-    });
-    for arg in args.iter() {
-        match arg {
-            arg if arg.starts_with("-g=") || arg.starts_with("--grammar=") => {
-                let grmf = if arg.starts_with("-g=") {
-                    arg.trim_start_matches("-g=")
-                } else {
-                    arg.trim_start_matches("--grammar=")
-                };
-                grm::pgrm(&mut code, grmf);
-                break;
-            }
-            _ => {}
-        }
     }
 
+    // Paths in the manifest are relative to the project, and flags given on the command
+    // line win over it
+    let grammar = manifest.grammar.map(|g| dir.join(g).display().to_string());
+    let code = read_file(&src, grammar.as_deref());
+    let opt_level = match parse_optimization(args) {
+        -1 => manifest.opt,
+        level => level,
+    };
+    let opts = BuildOpts {
+        output: target.join(&manifest.output),
+        dir: target,
+        opt_level,
+        static_link: manifest.static_link || args.contains(&"-static".to_string()),
+        c_sources: manifest.c_sources.iter().map(|c| dir.join(c)).collect(),
+        retain_c: retain_c(args),
+    };
+    compile(&code, &src, grammar.as_deref(), &opts);
+}
+
+fn build_file(args: &[String], src: &Path) {
+    println!("{} {}", "Building file :".green(), src.display());
+
+    let grammar = grammar_flag(args);
+    let code = read_file(src, grammar.as_deref());
+
+    println!("{}", "Parsing CLI arguments...".green());
+    let _target_os = parse_target_os(args);
+    let opts = BuildOpts {
+        output: PathBuf::from(parse_output(args).trim()),
+        dir: PathBuf::new(),
+        opt_level: parse_optimization(args),
+        static_link: args.contains(&"-static".to_string()),
+        c_sources: Vec::new(),
+        retain_c: retain_c(args),
+    };
+    compile(&code, src, grammar.as_deref(), &opts);
+}
+
+/// Turns the source of a program's main file into an executable. The grammar is applied to
+/// the modules it uses as well.
+fn compile(code: &str, src: &Path, grammar: Option<&str>, opts: &BuildOpts) {
     println!("{}", "Lexing file...".green());
     let mut toks = Tokens::new();
-    lex(&code, &mut toks);
+    lex(code, &mut toks);
     //println!("[DEBUG] toks : {:?}", toks);
 
     println!("{}", "Parsing file...".green());
@@ -84,27 +153,51 @@ fn build_file(args: &[String], src: &Path) {
         &toks,
         &code.split("\n").collect::<Vec<&str>>(),
         src.display().to_string().as_str(),
+        grammar,
         true,
         &mut Vec::new(),
     );
     //println!("[DEBUG] nst : {:?}", nst);
 
-    println!("{}", "Parsing CLI arguments...".green());
-    let _target_os = parse_target_os(args);
-    let opt_level = parse_optimization(args);
-    let output_file = parse_output(args).trim().to_string();
-
     println!("{}", "Generating code...".green());
     let ccode = codegen(&mut nst, true, true, true);
 
+    let output_file = opts.output.display().to_string();
     println!("{}", "Writing C code to file...".green());
     write_to_file(&ccode, &output_file);
 
     println!("{}", "Generating Clang command...".green());
-    let cmd = build_clang_command(args, &output_file, src, opt_level);
+    let cmd = build_clang_command(&output_file, opts);
 
     println!("{}", "Running Clang command...".green());
-    run_clang_command(cmd, &output_file, args);
+    run_clang_command(cmd, &output_file, opts);
+}
+
+fn retain_c(args: &[String]) -> bool {
+    args.contains(&"-rc".to_string()) || args.contains(&"--retian-c".to_string())
+}
+
+/// The grammar file passed with `-g=`/`--grammar=`, if any.
+pub fn grammar_flag(args: &[String]) -> Option<String> {
+    args.iter()
+        .find_map(|arg| parse_flag(arg, "-g=", "--grammar="))
+}
+
+/// Reads a source file and applies a grammar file to it, if one is given.
+pub fn read_file(src: &Path, grammar: Option<&str>) -> String {
+    let mut code = fs::read_to_string(src).unwrap_or_else(|e| {
+        eprintln!(
+            "{} {}",
+            "Error :~ Cannot read source file :".red(),
+            src.display()
+        );
+        eprintln!("{} {}", "Error MSG :~".red(), e.to_string().bright_red());
+        exit(-1);
+    });
+    if let Some(grmf) = grammar {
+        grm::pgrm(&mut code, grmf);
+    }
+    code
 }
 
 fn parse_target_os(args: &[String]) -> String {
@@ -151,9 +244,7 @@ fn parse_output(args: &[String]) -> String {
 }
 
 fn parse_flag(arg: &str, flag1: &str, flag2: &str) -> Option<String> {
-    if arg.starts_with(flag1) {
-        Some(arg.split('=').collect::<Vec<&str>>()[1].to_string())
-    } else if arg.starts_with(flag2) {
+    if arg.starts_with(flag1) || arg.starts_with(flag2) {
         Some(arg.split('=').collect::<Vec<&str>>()[1].to_string())
     } else {
         None
@@ -181,37 +272,40 @@ fn write_to_file(ccode: &str, output_file: &str) {
     }
 }
 
-fn build_clang_command(args: &[String], output_file: &str, _src: &Path, opt_level: i32) -> Command {
+fn build_clang_command(output_file: &str, opts: &BuildOpts) -> Command {
     let mut cmd = Command::new("clang");
     cmd.arg(format!("{}.c", output_file));
-    let nulibcp = Path::new("nulibc.c");
-    let nulibchp = Path::new("nulibc.h");
-    match File::create(nulibcp) {
+    let nulibcp = opts.dir.join("nulibc.c");
+    let nulibchp = opts.dir.join("nulibc.h");
+    match File::create(&nulibcp) {
         Ok(mut f) => {
             f.write_all(nulibc::NULIBC.as_bytes()).unwrap();
-            cmd.arg("nulibc.c");
+            cmd.arg(&nulibcp);
         }
         Err(e) => {
-            eprintln!("{}{}", "Error :~ Unable to create nulibc.c file :~ {}", e);
+            eprintln!("Error :~ Unable to create nulibc.c file :~ {}", e);
             exit(1);
         }
     }
-    match File::create(nulibchp) {
+    match File::create(&nulibchp) {
         Ok(mut f) => {
             f.write_all(nulibc::NULIBCH.as_bytes()).unwrap();
         }
         Err(e) => {
-            eprintln!("{}{}", "Error :~ Unable to create nulibc.c file :~ {}", e);
+            eprintln!("Error :~ Unable to create nulibc.h file :~ {}", e);
             exit(1);
         }
     }
+    cmd.args(&opts.c_sources);
     cmd.arg(format!("-o{}", output_file));
+    // Ints wrap around on overflow like in the interpreter, which C only promises with this
+    cmd.arg("-fwrapv");
 
-    if args.contains(&"-static".to_string()) {
+    if opts.static_link {
         cmd.arg("-static");
     }
 
-    let opt_flags = get_optimization_flags(opt_level);
+    let opt_flags = get_optimization_flags(opts.opt_level);
     cmd.args(opt_flags);
 
     //println!("[DEBUG] cmd: {:?}", cmd);
@@ -277,7 +371,7 @@ fn get_optimization_flags(level: i32) -> Vec<&'static str> {
     }
 }
 
-fn run_clang_command(mut cmd: Command, output_file: &str, args: &[String]) {
+fn run_clang_command(mut cmd: Command, output_file: &str, opts: &BuildOpts) {
     // println!("[DEBUG] cmd: {:?}", cmd);
 
     match cmd.status() {
@@ -287,7 +381,7 @@ fn run_clang_command(mut cmd: Command, output_file: &str, args: &[String]) {
                     "{}",
                     "Neit-2-C Converted Code compiled successfully!".green()
                 );
-                if !args.contains(&"-rc".to_string()) && !args.contains(&"--retian-c".to_string()) {
+                if !opts.retain_c {
                     match fs::remove_file(format!("{}.c", output_file)) {
                         Ok(_) => {}
                         Err(e) => {
@@ -296,7 +390,7 @@ fn run_clang_command(mut cmd: Command, output_file: &str, args: &[String]) {
                             exit(-1);
                         }
                     }
-                    match fs::remove_file(format!("nulibc.c")) {
+                    match fs::remove_file(opts.dir.join("nulibc.c")) {
                         Ok(_) => {}
                         Err(e) => {
                             eprintln!("{}", "Error :~ Cannot remove file : nulibc.c".red());
@@ -304,7 +398,7 @@ fn run_clang_command(mut cmd: Command, output_file: &str, args: &[String]) {
                             exit(-1);
                         }
                     }
-                    match fs::remove_file(format!("nulibc.h")) {
+                    match fs::remove_file(opts.dir.join("nulibc.h")) {
                         Ok(_) => {}
                         Err(e) => {
                             eprintln!("{}", "Error :~ Cannot remove file : nulibc.h".red());
@@ -1,14 +1,19 @@
-use crate::p::{VVal, Var, NST};
+use crate::{
+    expr::{BinOp, Builtin, Expr, UnOp},
+    p::{Param, Pat, PrintPart, Stmt, VVal, Var, NST},
+    p2::ValueType,
+    p3::step_sign,
+    scope::{ScopeKind, Symbols},
+};
 use colored::*;
-use std::collections::HashMap;
+use std::collections::HashSet;
 
 pub static INDENTLEV: &str = "    ";
 
-pub fn codegen(nst: &mut Vec<NST>, addh: bool, generate_main: bool, addstrcmp: bool) -> String {
+pub fn codegen(nst: &mut Vec<Stmt>, addh: bool, generate_main: bool, addstrcmp: bool) -> String {
     let mut ccode = String::new();
-    let mut vars: HashMap<String, VVal> = HashMap::new();
+    let mut vars = Symbols::new();
     let mut func_body = String::new();
-    let mut added_nclrscrn = false;
     if addstrcmp {
         //println!("[DEBUG] ~ adding strmp");
         ccode.push_str(r#""#);
@@ -17,82 +22,69 @@ pub fn codegen(nst: &mut Vec<NST>, addh: bool, generate_main: bool, addstrcmp: b
 
     if addh {
         println!("{}", "-> Adding headers".green().bold());
-        ccode.push_str("#include \"nulibc.h\"\n#include <stdio.h>\n#include <stdlib.h>\n#include <unistd.h>\n\n");
+        ccode.push_str("#include \"nulibc.h\"\n#include <stdbool.h>\n#include <math.h>\n#include <stdio.h>\n#include <stdlib.h>\n\n");
     }
 
-    if nst.iter().any(|mc| matches!(mc, NST::NCLRSCRN)) {
+    if nst.iter().any(|(_, mc)| matches!(mc, NST::NCLRSCRN)) {
         println!("{}", "-> Clearscreen function detected".blue());
     }
 
     println!("{}", "-> Walking down NST tree...".green().bold());
 
-    for mc in &mut *nst {
+    gen_types(nst, &mut HashSet::new(), &mut ccode);
+
+    // Top level variables are globals so commands can use them, and are set as `main` reaches
+    // their declarations
+    for (_, mc) in nst.iter() {
         match mc {
-            NST::NCLRSCRN => {
-                if !added_nclrscrn {
-                    ccode.push_str(&nclrscrn());
-                    added_nclrscrn = true;
-                }
-            }
-            NST::PRINT(txt) => {
-                let print_code = generate_print_code(txt, &vars);
-                func_body.push_str(&print_code);
+            NST::Var(v) => {
+                ccode.push_str(&format!("static {} {};\n", c_type(&v.ty), c_var(&v.name)))
             }
-            NST::Func(name, _args, nsts) => {
-                let body_code = codegen(nsts, false, false, false);
-                ccode.push_str(&format!("void {}() {{\n", name));
-                ccode.push_str(&body_code);
+            NST::Input(v) => ccode.push_str(&format!("static char *{};\n", c_var(v))),
+            _ => {}
+        }
+    }
+
+    // Prototypes let commands call each other regardless of definition order
+    for (_, mc) in nst.iter() {
+        if let NST::Func(name, args, ret, _) = mc {
+            ccode.push_str(&format!("{};\n", signature(name, args, ret)));
+        }
+    }
+
+    for (_, mc) in &mut *nst {
+        match mc {
+            NST::Func(name, args, ret, nsts) => {
+                let mut locals = vars.nested(ScopeKind::Cmd);
+                ccode.push_str(&format!("{} {{\n", signature(name, args, ret)));
+                for p in args.iter() {
+                    // String arguments are copied so the body can reassign them like any local,
+                    // and freed like one when the command returns
+                    if p.ty == ValueType::Str {
+                        ccode.push_str(&format!(
+                            "    char *{} __NDROP__(__NFREE__) = __NSTRDUP__(__arg_{});\n",
+                            c_var(&p.name),
+                            p.name
+                        ));
+                    }
+                    locals.declare(&p.name, p.ty.clone());
+                }
+                ccode.push_str(&gen_body(nsts, &mut locals));
                 ccode.push_str("}\n");
             }
             NST::Var(v) => {
-                vars.insert(v.name.clone(), v.value.clone());
-                func_body.push_str(&generate_var_code(v));
+                vars.declare(&v.name, v.ty.clone());
+                let value = match v.ty {
+                    ValueType::Str => c_owned(&v.value),
+                    _ => c_expr(&v.value),
+                };
+                func_body.push_str(&format!("    {} = {};\n", c_var(&v.name), value));
             }
             NST::Input(v) => {
-                func_body.push_str(&format!(
-                    "char {}[2048];\nscanf(\"%2047[^\\n]\", {});\n",
-                    v, v
-                ));
-                vars.insert(v.to_string(), VVal::Str(String::from("")));
-            }
-            NST::VRDInput(v) => {
-                func_body.push_str(&format!("   scanf(\"%2047[^\\n]\", {});\n", v));
-                vars.insert(v.to_string(), VVal::Str(String::from("")));
-            }
-            NST::WAIT(t) => {
-                func_body.push_str(format!("usleep({}LL*1000);\n", t).as_str());
-            }
-            NST::NIF(cond, code) => {
-                //println!("code : {:?}",code);
-                func_body.push_str(
-                    format!(
-                        "if ({}){{\n{}\n}}\n",
-                        cond.c_code,
-                        codegen(code, false, false, false)
-                    )
-                    .as_str(),
-                );
-            }
-            NST::VarRD(n, v) => match v {
-                VVal::Str(s) => {
-                    func_body.push_str(format!("{} = {};\n", n, s).as_str());
-                }
-                VVal::Int(i) => {
-                    func_body.push_str(format!("{} = {};\n", n, i).as_str());
-                }
-                VVal::F(f) => {
-                    func_body.push_str(format!("{} = {};\n", n, f).as_str());
-                }
-                VVal::VarRef(n2, _v2) => {
-                    func_body.push_str(format!("{} = {};\n", n, n2).as_str());
-                }
-            },
-            NST::NWHILE(cond, body) => {
-                let body = codegen(body, false, false, false);
-                func_body.push_str(&format!("while ({}) {{\n", cond.c_code));
-                func_body.push_str(&body);
-                func_body.push_str("}\n");
+                vars.declare(v, ValueType::Str);
+                func_body.push_str(&format!("    {} = __NTAKEIN__();\n", c_var(v)));
             }
+            _ => func_body.push_str(&gen_stmt(mc, &mut vars)),
         }
     }
 
@@ -108,79 +100,1098 @@ pub fn codegen(nst: &mut Vec<NST>, addh: bool, generate_main: bool, addstrcmp: b
     ccode
 }
 
-fn nclrscrn() -> String {
-    r#"
-"#
-    .to_string()
+/// Renders the C signature of a command, e.g. `int add(int a, int b)`.
+fn signature(name: &str, args: &[Param], ret: &Option<ValueType>) -> String {
+    let ret = match ret {
+        None => "void".to_string(),
+        Some(ty) => c_type(ty),
+    };
+    let params: Vec<String> = args
+        .iter()
+        .map(|p| match p.ty {
+            ValueType::Str => format!("const char *__arg_{}", p.name),
+            _ => format!("{} {}", c_type(&p.ty), c_var(&p.name)),
+        })
+        .collect();
+    if params.is_empty() {
+        format!("{} {}(void)", ret, c_cmd(name))
+    } else {
+        format!("{} {}({})", ret, c_cmd(name), params.join(", "))
+    }
 }
 
-fn generate_print_code(txt: &str, vars: &HashMap<String, VVal>) -> String {
-    let mut format_str = String::new();
-    let mut var_names: Vec<String> = Vec::new();
-    let mut in_var_mode = false;
-    let mut escape_mode = false;
-    let mut current_var = String::new();
-
-    for c in txt.chars() {
-        if escape_mode {
-            match c {
-                '{' | '}' | '\\' => format_str.push(c),
-                _ => {
-                    format_str.push('\\');
-                    format_str.push(c);
+/// The C name of a variable. Variables are prefixed so that names C already uses, such as
+/// `read`, `free` or `main`, stay free to use in Neit, where globals would clash with them.
+/// The temporaries of [`sequence`] are named `$0`, `$1`, ..., which no Neit variable can be.
+fn c_var(name: &str) -> String {
+    match name.strip_prefix('$') {
+        Some(n) => format!("__t{}", n),
+        None => format!("__nv_{}", name),
+    }
+}
+
+/// The C name of a command, prefixed like variables so that `exit` or `puts` can be commands.
+///
+/// Commands from modules are linked as `module.name`, and become `__nm_` followed by the length
+/// of the module's name, the module and the command, which cannot be read two ways.
+fn c_cmd(link: &str) -> String {
+    match link.split_once('.') {
+        Some((ns, name)) => format!("__nm_{}_{}_{}", ns.len(), ns, name),
+        None => format!("__nc_{}", link),
+    }
+}
+
+/// The C name of a shape or enum type, prefixed like variables.
+fn c_type_name(name: &str) -> String {
+    format!("__nt_{}", name)
+}
+
+/// The C name of the function that frees a shape when the variable holding it goes out of scope.
+fn c_drop(name: &str) -> String {
+    format!("__nd_{}", name)
+}
+
+/// The C name of a shape's field, prefixed so that fields can be called `default` or `char`.
+fn c_field(name: &str) -> String {
+    format!("__nf_{}", name)
+}
+
+/// The C name of an enum's variant. It goes by position, as joining the names of the enum and
+/// the variant could give the same name for two variants.
+fn c_variant(name: &str, idx: usize) -> String {
+    format!("__ne_{}_{}", name, idx)
+}
+
+/// The C type used for values of a Neit type. Strings live on the heap and are owned by the
+/// variable, field or element holding them, which frees the old text when reassigned.
+fn c_type(ty: &ValueType) -> String {
+    match ty {
+        ValueType::Str => "char *".to_string(),
+        ValueType::Float => "float".to_string(),
+        ValueType::Int => "int".to_string(),
+        ValueType::Bool => "bool".to_string(),
+        ValueType::List(_) => "__NLIST__ *".to_string(),
+        ValueType::Map(..) => "__NMAP__ *".to_string(),
+        ValueType::Shape(name) => format!("{} *", c_type_name(name)),
+        ValueType::Enum(name) => c_type_name(name),
+    }
+}
+
+/// Emits a C `struct` for every `shape` and a C `enum` with a table of variant names for
+/// every `enum`, wherever they were declared, so that the prototypes and all functions can
+/// use them.
+///
+/// Two files may declare the same type, which is emitted once; `use` has checked that both
+/// declarations agree.
+fn gen_types(nst: &[Stmt], seen: &mut HashSet<String>, out: &mut String) {
+    for (_, mc) in nst {
+        match mc {
+            NST::Shape(name, _) | NST::Enum(name, _) if !seen.insert(name.clone()) => {}
+            NST::Shape(shape, fields) => {
+                let name = c_type_name(shape);
+                out.push_str(&format!("typedef struct {} {{\n", name));
+                for f in fields {
+                    out.push_str(&format!("    {} {};\n", c_type(&f.ty), c_field(&f.name)));
+                }
+                out.push_str(&format!("}} {};\n", name));
+                // Frees a shape and its strings, but not the lists, maps or shapes it holds
+                out.push_str(&format!("static void {}({} **p) {{\n", c_drop(shape), name));
+                for f in fields.iter().filter(|f| f.ty == ValueType::Str) {
+                    out.push_str(&format!("    free((*p)->{});\n", c_field(&f.name)));
+                }
+                out.push_str("    free(*p);\n}\n");
+            }
+            NST::Enum(name, variants) => {
+                let cases: Vec<String> = (0..variants.len()).map(|i| c_variant(name, i)).collect();
+                let names: Vec<String> = variants.iter().map(|v| format!("\"{}\"", v)).collect();
+                out.push_str(&format!(
+                    "typedef enum {{ {} }} {};\nstatic const char *__NENUM_{}__[] = {{ {} }};\n",
+                    cases.join(", "),
+                    c_type_name(name),
+                    name,
+                    names.join(", ")
+                ));
+            }
+            NST::Func(_, _, _, body)
+            | NST::NWHILE(_, body)
+            | NST::NFOR(_, _, _, _, _, body)
+            | NST::NEACH(_, _, body) => gen_types(body, seen, out),
+            NST::NIF(_, body, elifs, else_body) => {
+                gen_types(body, seen, out);
+                for (_, _, body) in elifs {
+                    gen_types(body, seen, out);
+                }
+                if let Some(body) = else_body {
+                    gen_types(body, seen, out);
+                }
+            }
+            NST::Match(_, arms, default) => {
+                for (_, body) in arms {
+                    gen_types(body, seen, out);
+                }
+                if let Some(body) = default {
+                    gen_types(body, seen, out);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The `__NVAL__` field that holds a list element or map entry of the given type.
+fn val_field(ty: &ValueType) -> &'static str {
+    match ty {
+        ValueType::Str => "s",
+        ValueType::Float => "f",
+        ValueType::Int | ValueType::Enum(_) => "i",
+        ValueType::Bool => "b",
+        ValueType::List(_) | ValueType::Map(..) | ValueType::Shape(_) => "p",
+    }
+}
+
+/// Whether a string expression gives a fresh heap string that nothing else owns.
+///
+/// Concatenations, input, command results, popped elements and new strings from the string
+/// builtins are fresh; anything else may belong to a variable, list or shape that frees it.
+fn fresh(e: &Expr) -> bool {
+    match e {
+        Expr::Bin(_, BinOp::Add, _, ValueType::Str)
+        | Expr::Input
+        | Expr::Call(_, _, ValueType::Str)
+        | Expr::Builtin(Builtin::Pop, _, ValueType::Str)
+        | Expr::Builtin(
+            Builtin::Upper
+            | Builtin::Lower
+            | Builtin::Trim
+            | Builtin::Replace
+            | Builtin::Slice
+            | Builtin::ToStr,
+            ..,
+        ) => true,
+        Expr::Index(s, ..) => s.ty() == ValueType::Str,
+        _ => false,
+    }
+}
+
+/// Whether an expression gives a new list, map or shape, which no variable holds yet.
+fn fresh_container(e: &Expr) -> bool {
+    matches!(
+        e,
+        Expr::List(..) | Expr::Fill(..) | Expr::Map(..) | Expr::Shape(..)
+    ) || matches!(e, Expr::Builtin(Builtin::Split, ..))
+}
+
+/// Renders a string expression as a heap string that the caller owns, copying it unless it is
+/// already [`fresh`].
+fn c_owned(e: &Expr) -> String {
+    if fresh(e) {
+        c_expr(e)
+    } else {
+        format!("__NSTRDUP__({})", c_expr(e))
+    }
+}
+
+/// Moves the [`fresh`] strings among `ops`, and the [`fresh_container`]s too if `containers`,
+/// into temporaries that free them at the end of the enclosing block, for something that only
+/// reads them. Gives back their declarations.
+fn hoist(ops: Vec<&mut Expr>, containers: bool) -> Vec<String> {
+    let mut temps = Vec::new();
+    for (i, op) in ops.into_iter().enumerate() {
+        let owned = fresh(op) || containers && fresh_container(op);
+        if let (true, Some(f)) = (owned, drop_fn(&op.ty())) {
+            let t = format!("${}", i);
+            temps.push(format!(
+                "{} {} __NDROP__({}) = {};",
+                c_type(&op.ty()),
+                c_var(&t),
+                f,
+                c_expr(op)
+            ));
+            *op = Expr::Var(t, op.ty());
+        }
+    }
+    temps
+}
+
+/// The function that frees a value of `ty` held by a local variable, for the types that need
+/// one.
+fn drop_fn(ty: &ValueType) -> Option<String> {
+    match ty {
+        ValueType::Str => Some("__NFREE__".to_string()),
+        ValueType::List(elem) if **elem == ValueType::Str => {
+            Some("__NLIST_FREE_STRS__".to_string())
+        }
+        ValueType::List(_) => Some("__NLIST_FREE__".to_string()),
+        ValueType::Map(..) => Some("__NMAP_FREE__".to_string()),
+        ValueType::Shape(name) => Some(c_drop(name)),
+        ValueType::Int | ValueType::Float | ValueType::Bool | ValueType::Enum(_) => None,
+    }
+}
+
+/// Whether the value of `e` can be the list, map or shape held by the variable `name`.
+/// `push` and `remove` give back the container they change.
+fn holds(e: &Expr, name: &str) -> bool {
+    match e {
+        Expr::Var(n, _) => n == name,
+        Expr::Builtin(Builtin::Push | Builtin::Remove, args, _) => holds(&args[0], name),
+        _ => false,
+    }
+}
+
+/// Whether `e` stores the container held by the variable `name` where it can outlive the
+/// variable: in a list, map or shape, or in a command, which may keep it.
+fn keeps(e: &Expr, name: &str) -> bool {
+    let stores = matches!(
+        e,
+        Expr::Call(..) | Expr::List(..) | Expr::Fill(..) | Expr::Map(..) | Expr::Shape(..)
+    );
+    e.children().into_iter().enumerate().any(|(i, c)| {
+        let stored = stores || matches!(e, Expr::Builtin(Builtin::Push, ..)) && i == 1;
+        stored && holds(c, name) || keeps(c, name)
+    })
+}
+
+/// Whether the list, map or shape a variable is declared with can outlive it, going by the
+/// statements `nst` in its scope: copied to another variable, stored, passed to a command or
+/// given back. The variable being assigned a new value counts as well, as its old value would
+/// not be freed and the new one may be shared.
+fn escapes(name: &str, nst: &[Stmt]) -> bool {
+    let out = |e: &Expr| holds(e, name) || keeps(e, name);
+    nst.iter().any(|(_, mc)| match mc {
+        NST::Var(v) => out(&v.value),
+        NST::VarRD(n, value) => n == name || out(value),
+        NST::Give(e) => out(e),
+        NST::Call(_, args) => args.iter().any(out),
+        NST::SetIdx(target, idx, value) => keeps(target, name) || keeps(idx, name) || out(value),
+        NST::SetField(record, _, _, value) => keeps(record, name) || out(value),
+        NST::Eval(e) | NST::Match(e, ..) | NST::NEACH(_, e, _) if keeps(e, name) => true,
+        NST::PRINT(parts) => parts.iter().any(|p| match p {
+            PrintPart::Expr(e) => keeps(e, name),
+            PrintPart::Text(_) => false,
+        }),
+        NST::NIF(cond, body, elifs, else_body) => {
+            keeps(cond, name)
+                || escapes(name, body)
+                || elifs
+                    .iter()
+                    .any(|(_, cond, body)| keeps(cond, name) || escapes(name, body))
+                || else_body.as_deref().is_some_and(|b| escapes(name, b))
+        }
+        NST::NWHILE(cond, body) => keeps(cond, name) || escapes(name, body),
+        NST::NFOR(_, from, to, _, step, body) => {
+            keeps(from, name)
+                || keeps(to, name)
+                || step.as_ref().is_some_and(|s| keeps(s, name))
+                || escapes(name, body)
+        }
+        NST::NEACH(_, _, body) => escapes(name, body),
+        NST::Match(_, arms, default) => {
+            arms.iter().any(|(_, body)| escapes(name, body))
+                || default.as_deref().is_some_and(|b| escapes(name, b))
+        }
+        _ => false,
+    })
+}
+
+/// Whether working out an expression does something another part of the same statement could
+/// notice: running a command, reading input, changing a list or map, or stopping the program
+/// with a runtime error.
+fn acts(e: &Expr) -> bool {
+    match e {
+        Expr::Call(..) | Expr::Input | Expr::Index(..) => true,
+        Expr::Bin(_, BinOp::Div | BinOp::Mod, _, ValueType::Int) => true,
+        Expr::Builtin(Builtin::Push | Builtin::Pop | Builtin::Remove | Builtin::Slice, ..) => true,
+        Expr::Builtin(Builtin::ToInt | Builtin::ToFloat, args, ty) => {
+            matches!(
+                (args[0].ty(), ty),
+                (ValueType::Str, _) | (ValueType::Float, ValueType::Int)
+            ) || acts(&args[0])
+        }
+        e => e.children().into_iter().any(acts),
+    }
+}
+
+/// Works out `ops` into numbered temporaries from left to right, as the interpreter does, when
+/// the order C leaves open for the arguments of a call or the operands of an operator could
+/// change the result.
+///
+/// Gives back the declarations, the frees of the strings taken as copies, and variables that
+/// read the temporaries, to use in place of `ops`.
+fn sequence(ops: &[&Expr]) -> Option<(Vec<String>, Vec<String>, Vec<Expr>)> {
+    let fixed = |e: &&Expr| matches!(e, Expr::Lit(_) | Expr::Variant(..));
+    if !ops.iter().any(|e| acts(e)) || ops.iter().filter(|e| !fixed(e)).count() < 2 {
+        return None;
+    }
+    let mut temps = Vec::new();
+    let mut frees = Vec::new();
+    let mut vars = Vec::new();
+    for (i, e) in ops.iter().enumerate() {
+        let var = Expr::Var(format!("${}", i), e.ty());
+        let t = c_var(&format!("${}", i));
+        match e.ty() {
+            // A copy, so a later operand cannot change or free the text first
+            ValueType::Str => {
+                temps.push(format!("char *{} = {};", t, c_owned(e)));
+                frees.push(format!("free({});", t));
+            }
+            ty => temps.push(format!("{} {} = {};", c_type(&ty), t, c_expr(e))),
+        }
+        vars.push(var);
+    }
+    Some((temps, frees, vars))
+}
+
+/// Indents the statements of [`sequence`] as lines of a block.
+fn lines(stmts: &[String]) -> String {
+    stmts.iter().map(|s| format!("    {}\n", s)).collect()
+}
+
+/// Wraps an expression in an `__NVAL__` so it can be stored in a list or map of `elem`.
+fn c_val(e: &Expr, elem: &ValueType) -> String {
+    let value = match elem {
+        ValueType::Str => c_owned(e),
+        _ => c_expr(e),
+    };
+    format!("(__NVAL__){{.{} = {}}}", val_field(elem), value)
+}
+
+/// Wraps a map key for a lookup, which unlike [`c_val`] does not need a copy of strings.
+fn c_key(e: &Expr, key: &ValueType) -> String {
+    match key {
+        ValueType::Str => format!("(__NVAL__){{.s = (char *)({})}}", c_expr(e)),
+        _ => format!("(__NVAL__){{.i = {}}}", c_expr(e)),
+    }
+}
+
+/// Reads an element of type `elem` out of an `__NVAL__`.
+fn c_unval(val: String, elem: &ValueType) -> String {
+    match elem {
+        ValueType::List(_) | ValueType::Map(..) | ValueType::Shape(_) => {
+            format!("(({}){}.p)", c_type(elem), val)
+        }
+        _ => format!("({}.{})", val, val_field(elem)),
+    }
+}
+
+/// Describes a type for `__NSHOW__`: `i`, `f`, `b` or `s`, `[` and the element's kind for a
+/// list, and `{` and the key's and the value's kinds for a map.
+fn show_kind(ty: &ValueType) -> String {
+    match ty {
+        ValueType::Str => "s".to_string(),
+        ValueType::Float => "f".to_string(),
+        ValueType::Int => "i".to_string(),
+        ValueType::Bool => "b".to_string(),
+        ValueType::List(e) => format!("[{}", show_kind(e)),
+        ValueType::Map(k, v) => format!("{{{}{}", show_kind(k), show_kind(v)),
+        ValueType::Shape(_) | ValueType::Enum(_) => {
+            unreachable!("shapes and containers of enums are rejected in print statements")
+        }
+    }
+}
+
+/// Generates the body of a nested block, which sees the variables of its enclosing scope.
+fn gen_block(nst: &[Stmt], vars: &Symbols<ValueType>) -> String {
+    let mut vars = vars.nested(ScopeKind::Block);
+    gen_body(nst, &mut vars)
+}
+
+/// Generates the statements of a block or command, which share the scope `vars`. A variable
+/// declared here sees the statements after it, to tell whether it can free its value.
+fn gen_body(nst: &[Stmt], vars: &mut Symbols<ValueType>) -> String {
+    nst.iter()
+        .enumerate()
+        .map(|(i, (_, mc))| match mc {
+            NST::Var(v) => {
+                vars.declare(&v.name, v.ty.clone());
+                generate_var_code(v, &nst[i + 1..])
+            }
+            mc => gen_stmt(mc, vars),
+        })
+        .collect()
+}
+
+fn gen_stmt(mc: &NST, vars: &mut Symbols<ValueType>) -> String {
+    match mc {
+        NST::NCLRSCRN => "    __NCLRSCRN__();\n".to_string(),
+        NST::PRINT(parts) => generate_print_code(parts),
+        // Commands are hoisted out of `main` by `codegen`
+        NST::Func(..) => String::new(),
+        NST::Var(_) => unreachable!("declarations are generated by `gen_body` or `codegen`"),
+        NST::Input(v) => {
+            vars.declare(v, ValueType::Str);
+            format!(
+                "    char *{} __NDROP__(__NFREE__) = __NTAKEIN__();\n",
+                c_var(v)
+            )
+        }
+        NST::VRDInput(v) => {
+            format!(
+                "    {v} = __NSTR_SET__({v}, __NTAKEIN__());\n",
+                v = c_var(v)
+            )
+        }
+        NST::WAIT(t) => format!("__NWAIT__({}LL);\n", t),
+        NST::NIF(cond, code, elifs, else_body) => {
+            let mut c = format!("if ({}){{\n{}\n}}", c_expr(cond), gen_block(code, vars));
+            for (_, cond, code) in elifs {
+                c.push_str(&format!(
+                    " else if ({}){{\n{}\n}}",
+                    c_expr(cond),
+                    gen_block(code, vars)
+                ));
+            }
+            if let Some(code) = else_body {
+                c.push_str(&format!(" else {{\n{}\n}}", gen_block(code, vars)));
+            }
+            c.push('\n');
+            c
+        }
+        NST::VarRD(n, v) => match vars.get(n) {
+            // The old text is freed when the new one is set, so a command changing the
+            // variable must run before it is read
+            Some(ValueType::Str) if acts(v) => format!(
+                "    {{\n    char *{t} = {};\n    {n} = __NSTR_SET__({n}, {t});\n    }}\n",
+                c_owned(v),
+                t = c_var("$0"),
+                n = c_var(n)
+            ),
+            Some(ValueType::Str) => {
+                format!(
+                    "    {n} = __NSTR_SET__({n}, {});\n",
+                    c_owned(v),
+                    n = c_var(n)
+                )
+            }
+            _ => format!("    {} = {};\n", c_var(n), c_expr(v)),
+        },
+        NST::NWHILE(cond, body) => {
+            format!("while ({}) {{\n{}}}\n", c_expr(cond), gen_block(body, vars))
+        }
+        NST::NFOR(var, from, to, inclusive, step, body) => {
+            let mut body_vars = vars.nested(ScopeKind::Block);
+            body_vars.declare(var, ValueType::Int);
+            let i = c_var(var);
+            // The end and the step are evaluated once, before the first iteration
+            let mut init = format!(
+                "int {} = {}, __end_{} = {}",
+                i,
+                c_expr(from),
+                var,
+                c_expr(to)
+            );
+            let sign = step_sign(step.as_ref());
+            if let Some(step) = step {
+                let step = match sign {
+                    Some(_) => c_expr(step),
+                    None => format!("__NSTEP__({})", c_expr(step)),
+                };
+                init.push_str(&format!(", __step_{} = {}", var, step));
+            }
+            let (up, down) = if *inclusive { ("<=", ">=") } else { ("<", ">") };
+            let cond = match sign {
+                Some(s) if s > 0 => format!("{} {} __end_{}", i, up, var),
+                Some(_) => format!("{} {} __end_{}", i, down, var),
+                None => format!(
+                    "__step_{v} > 0 ? {i} {} __end_{v} : {i} {} __end_{v}",
+                    up,
+                    down,
+                    i = i,
+                    v = var
+                ),
+            };
+            // A step past the ends of int can only overshoot the range, so it ends the loop
+            // instead of wrapping around. The distance left is worked out in a long long, as
+            // it does not always fit in an int
+            init.push_str(&format!(", __more_{} = 1", var));
+            let step = match step {
+                Some(_) => format!("__step_{}", var),
+                None => "1".to_string(),
+            };
+            let left = format!("(long long)__end_{} - {}", var, i);
+            let more = match sign {
+                Some(s) if s > 0 => format!("{} >= {}", left, step),
+                Some(_) => format!("{} <= {}", left, step),
+                None => format!("({s} > 0 ? {l} >= {s} : {l} <= {s})", s = step, l = left),
+            };
+            let inc = format!(
+                "__more_{v} = {}, {i} += __more_{v} ? {} : 0",
+                more,
+                step,
+                i = i,
+                v = var
+            );
+            format!(
+                "for ({}; __more_{} && ({}); {}) {{\n{}}}\n",
+                init,
+                var,
+                cond,
+                inc,
+                gen_block(body, &body_vars)
+            )
+        }
+        NST::NEACH(var, list, body) => {
+            // Lists give their elements and maps their keys
+            let (elem, item) = match list.ty() {
+                ValueType::List(elem) => (*elem, format!("__each_{}->items[__i_{}]", var, var)),
+                ValueType::Map(key, _) => {
+                    (*key, format!("__each_{}->entries[__i_{}].key", var, var))
+                }
+                _ => unreachable!("`for ... in` over a value that is not a list or a map"),
+            };
+            let mut body_vars = vars.nested(ScopeKind::Block);
+            body_vars.declare(var, elem.clone());
+            let item = c_unval(item, &elem);
+            let decl = match elem {
+                ValueType::Str => format!(
+                    "    char *{} __NDROP__(__NFREE__) = __NSTRDUP__({});\n",
+                    c_var(var),
+                    item
+                ),
+                ref ty => format!("    {} {} = {};\n", c_type(ty), c_var(var), item),
+            };
+            // A list made only to loop over is freed after the loop
+            let drop = match drop_fn(&list.ty()) {
+                Some(f) if fresh_container(list) => format!(" __NDROP__({})", f),
+                _ => String::new(),
+            };
+            // The braces keep `__each_x` from clashing with another loop over `x`
+            format!(
+                "{{\n{} __each_{v}{} = {};\nfor (int __i_{v} = 0; __i_{v} < __each_{v}->len; __i_{v}++) {{\n{}{}}}\n}}\n",
+                c_type(&list.ty()),
+                drop,
+                c_expr(list),
+                decl,
+                gen_block(body, &body_vars),
+                v = var
+            )
+        }
+        NST::SetIdx(target, idx, value) => {
+            let set = |target: &Expr, idx: &Expr, value: &Expr| match target.ty() {
+                // The element frees the string it held
+                ValueType::List(elem) if *elem == ValueType::Str => format!(
+                    "    {{\n    __NVAL__ *__e = __NLIST_AT__({}, {});\n    \
+                     __e->s = __NSTR_SET__(__e->s, {});\n    }}\n",
+                    c_expr(target),
+                    c_expr(idx),
+                    c_owned(value)
+                ),
+                ValueType::List(elem) => format!(
+                    "    *__NLIST_AT__({}, {}) = {};\n",
+                    c_expr(target),
+                    c_expr(idx),
+                    c_val(value, &elem)
+                ),
+                ValueType::Map(key, val) => format!(
+                    "    __NMAP_SET__({}, {}, {});\n",
+                    c_expr(target),
+                    c_val(idx, &key),
+                    c_val(value, &val)
+                ),
+                _ => unreachable!("index assignment on a value that is not a list or a map"),
+            };
+            // A value that runs a command goes into a temporary first, as C would be free to
+            // find the element before the command changes the list
+            match sequence(&[target, idx, value]) {
+                Some((temps, frees, ops)) => format!(
+                    "    {{\n{}{}{}    }}\n",
+                    lines(&temps),
+                    set(&ops[0], &ops[1], &ops[2]),
+                    lines(&frees)
+                ),
+                None => set(target, idx, value),
+            }
+        }
+        // A fresh string nothing takes is freed right away
+        NST::Eval(e) if fresh(e) => format!("    free({});\n", c_expr(e)),
+        NST::Eval(e) => format!("    {};\n", c_expr(e)),
+        // Emitted ahead of everything else by `gen_types`
+        NST::Shape(..) | NST::Enum(..) => String::new(),
+        NST::Match(value, arms, default) => gen_match(value, arms, default, vars),
+        NST::SetField(record, field, _, value) => {
+            let set = |record: &Expr, value: &Expr| match field.ty {
+                ValueType::Str => format!(
+                    "    {r}->{f} = __NSTR_SET__({r}->{f}, {});\n",
+                    c_owned(value),
+                    r = c_expr(record),
+                    f = c_field(&field.name)
+                ),
+                _ => format!(
+                    "    {}->{} = {};\n",
+                    c_expr(record),
+                    c_field(&field.name),
+                    c_expr(value)
+                ),
+            };
+            match sequence(&[record, value]) {
+                Some((temps, frees, ops)) => format!(
+                    "    {{\n{}{}{}    }}\n",
+                    lines(&temps),
+                    set(&ops[0], &ops[1]),
+                    lines(&frees)
+                ),
+                None => set(record, value),
+            }
+        }
+        NST::Call(name, args) => match sequence(&args.iter().collect::<Vec<_>>()) {
+            Some((temps, frees, args)) => {
+                let args: Vec<String> = args.iter().map(c_expr).collect();
+                format!(
+                    "    {{\n{}    {}({});\n{}    }}\n",
+                    lines(&temps),
+                    c_cmd(name),
+                    args.join(", "),
+                    lines(&frees)
+                )
+            }
+            None => {
+                let mut args = args.clone();
+                let temps = hoist(args.iter_mut().collect(), false);
+                let args: Vec<String> = args.iter().map(c_expr).collect();
+                let call = format!("    {}({});\n", c_cmd(name), args.join(", "));
+                if temps.is_empty() {
+                    call
+                } else {
+                    format!("    {{\n{}{}    }}\n", lines(&temps), call)
+                }
+            }
+        },
+        // Callers own returned strings, like any other fresh string
+        NST::Give(v) if v.ty() == ValueType::Str => format!("    return {};\n", c_owned(v)),
+        NST::Give(v) => format!("    return {};\n", c_expr(v)),
+        NST::Break => "    break;\n".to_string(),
+        NST::Continue => "    continue;\n".to_string(),
+    }
+}
+
+/// Lowers a `match` to a C `switch` when every pattern is a plain enum variant or int, and
+/// to a chain of `if`s otherwise.
+fn gen_match(
+    value: &Expr,
+    arms: &[(Vec<Pat>, Vec<Stmt>)],
+    default: &Option<Vec<Stmt>>,
+    vars: &Symbols<ValueType>,
+) -> String {
+    let ty = value.ty();
+    let plain = !matches!(ty, ValueType::Str)
+        && arms
+            .iter()
+            .flat_map(|(p, _)| p)
+            .all(|p| matches!(p, Pat::Val(_)));
+    // A `break` inside a `switch` would leave the `switch` rather than the loop around it
+    let breaks = arms
+        .iter()
+        .map(|(_, body)| body)
+        .chain(default)
+        .any(|body| breaks_out(body));
+
+    if plain && !breaks {
+        let mut c = format!("    switch ({}) {{\n", c_expr(value));
+        for (pats, body) in arms {
+            for p in pats {
+                if let Pat::Val(e) = p {
+                    c.push_str(&format!("    case {}:\n", c_expr(e)));
                 }
             }
-            escape_mode = false;
-        } else if c == '\\' {
-            escape_mode = true;
-        } else if c == '{' {
-            if in_var_mode {
-                current_var.push(c);
+            c.push_str(&format!(
+                "    {{\n{}\n    }} break;\n",
+                gen_block(body, vars)
+            ));
+        }
+        if let Some(body) = default {
+            c.push_str(&format!(
+                "    default: {{\n{}\n    }} break;\n",
+                gen_block(body, vars)
+            ));
+        }
+        c.push_str("    }\n");
+        return c;
+    }
+
+    let mut c = match ty {
+        // A copy, which the arms cannot change or free by assigning to the matched variable
+        ValueType::Str => format!(
+            "    {{\n    char *__m __NDROP__(__NFREE__) = {};\n",
+            c_owned(value)
+        ),
+        _ => format!("    {{\n    {} __m = {};\n", c_type(&ty), c_expr(value)),
+    };
+    for (i, (pats, body)) in arms.iter().enumerate() {
+        let conds: Vec<String> = pats
+            .iter()
+            .map(|p| match (p, &ty) {
+                (Pat::Range(lo, hi), _) => format!("(__m >= {} && __m <= {})", lo, hi),
+                (Pat::Val(e), ValueType::Str) => format!("strcmp(__m, {}) == 0", c_expr(e)),
+                (Pat::Val(e), _) => format!("__m == {}", c_expr(e)),
+            })
+            .collect();
+        c.push_str(&format!(
+            "    {}if ({}) {{\n{}\n    }}",
+            if i == 0 { "" } else { " else " },
+            conds.join(" || "),
+            gen_block(body, vars)
+        ));
+    }
+    if let Some(body) = default {
+        if arms.is_empty() {
+            c.push_str(&format!("    {{\n{}\n    }}", gen_block(body, vars)));
+        } else {
+            c.push_str(&format!(" else {{\n{}\n    }}", gen_block(body, vars)));
+        }
+    }
+    c.push_str("\n    }\n");
+    c
+}
+
+/// Whether a block has a `break` that belongs to a loop outside of it.
+fn breaks_out(nst: &[Stmt]) -> bool {
+    nst.iter().any(|(_, mc)| match mc {
+        NST::Break => true,
+        NST::NIF(_, body, elifs, else_body) => {
+            breaks_out(body)
+                || elifs.iter().any(|(_, _, body)| breaks_out(body))
+                || else_body.as_deref().is_some_and(breaks_out)
+        }
+        NST::Match(_, arms, default) => {
+            arms.iter().any(|(_, body)| breaks_out(body))
+                || default.as_deref().is_some_and(breaks_out)
+        }
+        _ => false,
+    })
+}
+
+/// Renders an expression as C.
+fn c_expr(e: &Expr) -> String {
+    // `&&` and `||` already work out their left side first, and may skip the right one
+    if !matches!(e, Expr::Bin(_, BinOp::And | BinOp::Or, ..)) {
+        if let Some((temps, frees, vars)) = sequence(&e.children()) {
+            let mut e = e.clone();
+            for (op, var) in e.children_mut().into_iter().zip(vars) {
+                *op = var;
+            }
+            // A GNU statement expression, as the temporaries are needed where only an
+            // expression fits
+            return if frees.is_empty() {
+                format!("({{ {} {}; }})", temps.join(" "), c_expr(&e))
             } else {
-                in_var_mode = true;
-                current_var.clear();
-            }
-        } else if c == '}' {
-            if in_var_mode {
-                in_var_mode = false;
-
-                if let Some(var_val) = vars.get(&current_var) {
-                    match var_val {
-                        VVal::Str(_) => format_str.push_str("%s"),
-                        VVal::Int(_) => format_str.push_str("%d"),
-                        VVal::F(_) => format_str.push_str("%f"),
-                        VVal::VarRef(_, _) => format_str.push_str("%d"),
-                    }
-                    var_names.push(current_var.clone());
+                format!(
+                    "({{ {} {} __r = {}; {} __r; }})",
+                    temps.join(" "),
+                    c_type(&e.ty()),
+                    c_expr(&e),
+                    frees.join(" ")
+                )
+            };
+        }
+    }
+    // Lists, maps, shapes and `push` take the fresh values they are given, while anything else
+    // only reads them and frees them afterwards. A new list, map or shape must outlive a call,
+    // which may keep it, `remove`, which gives it back, and reading a string out of it
+    let takes = matches!(
+        e,
+        Expr::List(..) | Expr::Fill(..) | Expr::Map(..) | Expr::Shape(..)
+    ) || matches!(e, Expr::Builtin(Builtin::Push, ..));
+    let borrows = matches!(e, Expr::Call(..) | Expr::Builtin(Builtin::Remove, ..))
+        || matches!(e, Expr::Index(..) | Expr::Field(..)) && e.ty() == ValueType::Str;
+    let owned = |c: &Expr| fresh(c) || !borrows && fresh_container(c);
+    if !takes && e.children().into_iter().any(owned) {
+        let mut e = e.clone();
+        let temps = hoist(e.children_mut(), !borrows);
+        return format!("({{ {} {}; }})", temps.join(" "), c_expr(&e));
+    }
+    match e {
+        Expr::Lit(VVal::Str(s)) => format!("\"{}\"", c_escape(s)),
+        // `-2147483648` would be the negation of a literal too big for an `int`
+        Expr::Lit(VVal::Int(i32::MIN)) => "(-2147483647 - 1)".to_string(),
+        Expr::Lit(VVal::Int(i)) => i.to_string(),
+        // A constant can fold to NaN or an infinity, which have no literal of their own
+        Expr::Lit(VVal::F(f)) if f.is_nan() && f.is_sign_negative() => "(-NAN)".to_string(),
+        Expr::Lit(VVal::F(f)) if f.is_nan() => "NAN".to_string(),
+        Expr::Lit(VVal::F(f)) if f.is_infinite() && *f < 0.0 => "(-INFINITY)".to_string(),
+        Expr::Lit(VVal::F(f)) if f.is_infinite() => "INFINITY".to_string(),
+        // `{:?}` always keeps a decimal point and the suffix keeps the arithmetic in `float`
+        Expr::Lit(VVal::F(f)) => format!("{:?}f", f),
+        Expr::Lit(VVal::Bool(b)) => b.to_string(),
+        Expr::Lit(VVal::List(_) | VVal::Map(_) | VVal::Shape(_) | VVal::Enum(..)) => {
+            unreachable!("lists, maps, shapes and enums are built by their own Expr variants")
+        }
+        Expr::Var(n, _) => c_var(n),
+        Expr::Bin(l, BinOp::Add, r, ValueType::Str) => {
+            format!("__NSTRCAT__({}, {})", c_expr(l), c_expr(r))
+        }
+        Expr::Bin(l, op, r, _) if op.is_comparison() && l.ty() == ValueType::Str => {
+            format!("(strcmp({}, {}) {} 0)", c_expr(l), c_expr(r), op.symbol())
+        }
+        // Checked, as dividing an int by 0 would otherwise kill the program without a message
+        Expr::Bin(l, op @ (BinOp::Div | BinOp::Mod), r, ValueType::Int) => format!(
+            "{}({}, {})",
+            if *op == BinOp::Div {
+                "__NDIV__"
+            } else {
+                "__NMOD__"
+            },
+            c_expr(l),
+            c_expr(r)
+        ),
+        Expr::Bin(l, op, r, _) => format!("({} {} {})", c_expr(l), op.symbol(), c_expr(r)),
+        Expr::Un(UnOp::Neg, e, _) => format!("(-{})", c_expr(e)),
+        Expr::Un(UnOp::Not, e, _) => format!("(!{})", c_expr(e)),
+        Expr::Call(name, args, _) => {
+            let args: Vec<String> = args.iter().map(c_expr).collect();
+            format!("{}({})", c_cmd(name), args.join(", "))
+        }
+        Expr::Input => "__NTAKEIN__()".to_string(),
+        Expr::List(items, _) if items.is_empty() => "__NLIST_NEW__()".to_string(),
+        Expr::List(items, ty) => {
+            let ValueType::List(elem) = ty else {
+                unreachable!("a list literal always has a list type")
+            };
+            let items: Vec<String> = items.iter().map(|e| c_val(e, elem)).collect();
+            format!("__NLIST_OF__({}, {})", items.len(), items.join(", "))
+        }
+        Expr::Fill(value, n, ty) => {
+            let ValueType::List(elem) = ty else {
+                unreachable!("a list literal always has a list type")
+            };
+            format!(
+                "__NLIST_FILL__({}, {}, {})",
+                n,
+                c_val(value, elem),
+                **elem == ValueType::Str
+            )
+        }
+        Expr::Map(pairs, ty) => {
+            let ValueType::Map(key, val) = ty else {
+                unreachable!("a map literal always has a map type")
+            };
+            let kind = format!("'{}', '{}'", show_kind(key), val_field(val));
+            if pairs.is_empty() {
+                return format!("__NMAP_NEW__({})", kind);
+            }
+            let pairs: Vec<String> = pairs
+                .iter()
+                .map(|(k, v)| format!("{}, {}", c_val(k, key), c_val(v, val)))
+                .collect();
+            format!(
+                "__NMAP_OF__({}, {}, {})",
+                kind,
+                pairs.len(),
+                pairs.join(", ")
+            )
+        }
+        // Shapes live on the heap, so they can be shared and stored in lists like other values
+        Expr::Shape(name, fields) => {
+            let fields: Vec<String> = fields
+                .iter()
+                .map(|(f, v)| match f.ty {
+                    ValueType::Str => format!(".{} = {}", c_field(&f.name), c_owned(v)),
+                    _ => format!(".{} = {}", c_field(&f.name), c_expr(v)),
+                })
+                .collect();
+            format!(
+                "(({n} *)__NBOX__(&({n}){{{}}}, sizeof({n})))",
+                fields.join(", "),
+                n = c_type_name(name)
+            )
+        }
+        Expr::Field(record, field, _, _) => format!("({}->{})", c_expr(record), c_field(field)),
+        Expr::Variant(name, _, idx) => c_variant(name, *idx),
+        Expr::Index(target, idx, _) if target.ty() == ValueType::Str => {
+            format!("__NSTR_AT__({}, {})", c_expr(target), c_expr(idx))
+        }
+        Expr::Index(target, idx, elem) => {
+            let at = match target.ty() {
+                ValueType::Map(key, _) => {
+                    format!("__NMAP_AT__({}, {})", c_expr(target), c_key(idx, &key))
                 }
+                _ => format!("__NLIST_AT__({}, {})", c_expr(target), c_expr(idx)),
+            };
+            c_unval(format!("(*{})", at), elem)
+        }
+        Expr::Builtin(Builtin::Len, args, _) => match args[0].ty() {
+            ValueType::Map(..) => format!("__NMAP_LEN__({})", c_expr(&args[0])),
+            ValueType::Str => format!("__NSTR_LEN__({})", c_expr(&args[0])),
+            _ => format!("__NLIST_LEN__({})", c_expr(&args[0])),
+        },
+        Expr::Builtin(b @ (Builtin::Has | Builtin::Remove), args, _) => {
+            let ValueType::Map(key, _) = args[0].ty() else {
+                unreachable!("`{}` on a value that is not a map", b.name())
+            };
+            let func = if *b == Builtin::Has {
+                "__NMAP_HAS__"
             } else {
-                format_str.push(c);
+                "__NMAP_REMOVE__"
+            };
+            format!("{}({}, {})", func, c_expr(&args[0]), c_key(&args[1], &key))
+        }
+        Expr::Builtin(Builtin::Pop, args, elem) => {
+            c_unval(format!("__NLIST_POP__({})", c_expr(&args[0])), elem)
+        }
+        Expr::Builtin(Builtin::Push, args, ty) => {
+            let ValueType::List(elem) = ty else {
+                unreachable!("push always gives back a list")
+            };
+            format!(
+                "__NLIST_PUSH__({}, {})",
+                c_expr(&args[0]),
+                c_val(&args[1], elem)
+            )
+        }
+        Expr::Builtin(Builtin::ToInt | Builtin::ToFloat, args, ty) => match (args[0].ty(), ty) {
+            (ValueType::Str, ValueType::Int) => format!("__NTO_INT__({})", c_expr(&args[0])),
+            (ValueType::Str, _) => format!("__NTO_FLOAT__({})", c_expr(&args[0])),
+            (ValueType::Float, ValueType::Int) => format!("__NFLOAT_INT__({})", c_expr(&args[0])),
+            (_, ty) => format!("(({})({}))", c_type(ty), c_expr(&args[0])),
+        },
+        Expr::Builtin(Builtin::ToStr, args, _) => match args[0].ty() {
+            ValueType::Str => format!("__NSTRDUP__({})", c_expr(&args[0])),
+            ValueType::Enum(name) => {
+                format!("__NSTRDUP__(__NENUM_{}__[{}])", name, c_expr(&args[0]))
             }
-        } else if in_var_mode {
-            current_var.push(c);
-        } else {
-            format_str.push(c);
+            ty => format!(
+                "__NSHOW__((__NVAL__){{.{} = {}}}, \"{}\")",
+                val_field(&ty),
+                c_expr(&args[0]),
+                show_kind(&ty)
+            ),
+        },
+        Expr::Builtin(b, args, _) => {
+            let func = match b {
+                Builtin::Upper => "__NSTR_UPPER__",
+                Builtin::Lower => "__NSTR_LOWER__",
+                Builtin::Trim => "__NSTR_TRIM__",
+                Builtin::Contains => "__NSTR_HAS__",
+                Builtin::StartsWith => "__NSTR_STARTS__",
+                Builtin::EndsWith => "__NSTR_ENDS__",
+                Builtin::Replace => "__NSTR_REPLACE__",
+                Builtin::Split => "__NSTR_SPLIT__",
+                Builtin::Slice => "__NSTR_SLICE__",
+                _ => unreachable!("`{}` on a string", b.name()),
+            };
+            let args: Vec<String> = args.iter().map(c_expr).collect();
+            format!("{}({})", func, args.join(", "))
+        }
+    }
+}
+
+/// Escapes text for use inside a C string literal.
+fn c_escape(s: &str) -> String {
+    let mut out = String::new();
+    for c in s.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '"' => out.push_str("\\\""),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            c => out.push(c),
         }
     }
+    out
+}
 
-    if !var_names.is_empty() {
-        format!(
-            "    printf(\"{}\", {});\n    fflush(stdout);\n",
-            format_str,
-            var_names.join(", ")
-        )
-    } else {
-        format!("    printf(\"{}\");\n    fflush(stdout);\n", format_str)
+/// The `printf` conversion for a value of the given type.
+fn format_spec(ty: &ValueType) -> &'static str {
+    match ty {
+        ValueType::Str
+        | ValueType::Bool
+        | ValueType::List(_)
+        | ValueType::Map(..)
+        | ValueType::Shape(_)
+        | ValueType::Enum(_) => "%s",
+        ValueType::Float => "%f",
+        ValueType::Int => "%d",
     }
 }
 
-fn generate_var_code(v: &Var) -> String {
-    match &v.value {
-        VVal::Str(s) => {
-            format!("    char {}[2048] = \"{}\";\n", v.name, s)
+/// Prints text and interpolated values with one `printf`.
+///
+/// C leaves the order of a call's arguments open, so every value is first worked out into a
+/// numbered temporary from left to right, as the interpreter does. Strings and shown lists and
+/// maps are taken as copies, which a later value cannot change or free before they print.
+fn generate_print_code(parts: &[PrintPart]) -> String {
+    let mut format_str = String::new();
+    let mut temps = String::new();
+    let mut args: Vec<String> = Vec::new();
+    let mut owned: Vec<String> = Vec::new();
+
+    for part in parts {
+        match part {
+            PrintPart::Text(t) => format_str.push_str(&c_escape(t).replace('%', "%%")),
+            PrintPart::Expr(e) => {
+                let t = format!("__p{}", args.len());
+                format_str.push_str(format_spec(&e.ty()));
+                let arg = match e.ty() {
+                    ValueType::Bool => {
+                        temps.push_str(&format!("    bool {} = {};\n", t, c_expr(e)));
+                        format!("{} ? \"true\" : \"false\"", t)
+                    }
+                    ValueType::List(_) | ValueType::Map(..) => {
+                        // Shown as `str` shows it, which frees a list or map made to be printed
+                        let shown = Expr::Builtin(Builtin::ToStr, vec![e.clone()], ValueType::Str);
+                        temps.push_str(&format!("    char *{} = {};\n", t, c_expr(&shown)));
+                        owned.push(t.clone());
+                        t
+                    }
+                    ValueType::Enum(name) => {
+                        temps.push_str(&format!(
+                            "    const char *{} = __NENUM_{}__[{}];\n",
+                            t,
+                            name,
+                            c_expr(e)
+                        ));
+                        t
+                    }
+                    ValueType::Str => {
+                        temps.push_str(&format!("    char *{} = {};\n", t, c_owned(e)));
+                        owned.push(t.clone());
+                        t
+                    }
+                    ty => {
+                        temps.push_str(&format!("    {} {} = {};\n", c_type(&ty), t, c_expr(e)));
+                        t
+                    }
+                };
+                args.push(arg);
+            }
         }
-        VVal::Int(i) => format!("    int {} = {};\n", v.name, i),
-        VVal::F(f) => format!("    float {} = {};\n", v.name, f),
-        VVal::VarRef(_, _) => String::new(),
     }
+
+    if args.is_empty() {
+        return format!("    printf(\"{}\");\n    fflush(stdout);\n", format_str);
+    }
+    let frees: String = owned
+        .iter()
+        .map(|t| format!("    free({});\n", t))
+        .collect();
+    // The braces keep the temporaries of one print apart from the next
+    format!(
+        "    {{\n{}    printf(\"{}\", {});\n    fflush(stdout);\n{}    }}\n",
+        temps,
+        format_str,
+        args.join(", "),
+        frees
+    )
+}
+
+/// Declares a local variable, which frees its string when it goes out of scope, and its list,
+/// map or shape when it was made for the variable and nothing in `rest` can keep it.
+fn generate_var_code(v: &Var, rest: &[Stmt]) -> String {
+    let drop = match drop_fn(&v.ty) {
+        Some(f) if v.ty == ValueType::Str => format!(" __NDROP__({})", f),
+        Some(f) if fresh_container(&v.value) && !escapes(&v.name, rest) => {
+            format!(" __NDROP__({})", f)
+        }
+        _ => String::new(),
+    };
+    let value = match v.ty {
+        ValueType::Str => c_owned(&v.value),
+        _ => c_expr(&v.value),
+    };
+    format!(
+        "    {} {}{} = {};\n",
+        c_type(&v.ty),
+        c_var(&v.name),
+        drop,
+        value
+    )
 }
@@ -3,8 +3,6 @@ use colored::*;
 #[derive(Clone, Debug)]
 #[allow(unused)]
 /// Enum representing various error types that can occur during parsing.
-#[allow(unused)]
-/// Enum representing various error types that can occur during parsing.
 pub enum ErrT {
     /// Represents an invalid value assigned to a variable.
     /// Holds:
@@ -63,10 +61,126 @@ pub enum ErrT {
     /// Holds:
     /// - `usize`: Line number where the error occurred.
     InvalidCondOp(usize, String),
+
+    /// Represents an operator applied to values of the wrong type, e.g. `"a" * 2`.
+    /// Holds:
+    /// - `usize`: Line number where the error occurred.
+    /// - `String`: Which operator and operands were rejected.
     InvalidOperand(usize, String),
+
+    /// Represents an `else` or `else if` that does not follow an `if` body.
+    /// Holds:
+    /// - `usize`: Line number where the error occurred.
+    /// - `String`: The offending code.
+    DanglingElse(usize, String),
+
+    /// Represents a `call` to a command that has not been defined.
+    /// Holds:
+    /// - `usize`: Line number where the error occurred.
+    /// - `String`: The name of the command.
+    CmdNF(usize, String),
+
+    /// Represents a `call` with the wrong number of arguments.
+    /// Holds:
+    /// - `usize`: Line number where the error occurred.
+    /// - `String`: The name of the command.
+    /// - `usize`: Number of parameters the command declares.
+    /// - `usize`: Number of arguments given.
+    ArgCount(usize, String, usize, usize),
+
+    /// Represents a missing or unknown type.
+    /// Holds:
+    /// - `usize`: Line number where the error occurred.
+    /// - `String`: What is being typed, e.g. ``the parameter `age` ``.
+    /// - `String`: The type as written, e.g. `number`.
+    UnknownType(usize, String, String),
+
+    /// Represents a misplaced or mistyped `give`.
+    /// Holds:
+    /// - `usize`: Line number where the error occurred.
+    /// - `String`: What is wrong with the `give`.
+    BadGive(usize, String),
+
+    /// Represents a malformed `for` loop header.
+    /// Holds:
+    /// - `usize`: Line number where the error occurred.
+    /// - `String`: What is wrong with the header.
+    BadFor(usize, String),
+
+    /// Represents a `break` or `continue` outside of a loop body.
+    /// Holds:
+    /// - `usize`: Line number where the error occurred.
+    /// - `String`: The keyword that was used.
+    NotInLoop(usize, String),
+
+    /// Represents a malformed `shape` declaration, construction or field access.
+    /// Holds:
+    /// - `usize`: Line number where the error occurred.
+    /// - `String`: What is wrong.
+    BadShape(usize, String),
+
+    /// Represents a malformed `enum` declaration or an unknown variant.
+    /// Holds:
+    /// - `usize`: Line number where the error occurred.
+    /// - `String`: What is wrong.
+    BadEnum(usize, String),
+
+    /// Represents a malformed `match`, including one that misses variants of an enum.
+    /// Holds:
+    /// - `usize`: Line number where the error occurred.
+    /// - `String`: What is wrong.
+    BadMatch(usize, String),
+
+    /// Represents a `use` that cannot be resolved, forms a cycle or names a broken module,
+    /// and statements a module is not allowed to hold.
+    /// Holds:
+    /// - `usize`: Line number where the error occurred.
+    /// - `String`: What is wrong.
+    BadUse(usize, String),
+
+    /// Represents a declaration that clashes with another name in its scope, with a global or
+    /// with a command, or a command declared inside another body.
+    /// Holds:
+    /// - `usize`: Line number where the error occurred.
+    /// - `String`: What is wrong.
+    BadScope(usize, String),
+
+    /// Represents a `const` whose value cannot be worked out while compiling, a change to a
+    /// constant, or a list size that is not a constant.
+    /// Holds:
+    /// - `usize`: Line number where the error occurred.
+    /// - `String`: What is wrong.
+    BadConst(usize, String),
+
+    /// Represents a value whose type does not fit where it is stored or used: a variable, a
+    /// list or map element, a shape field, or a condition that is not a bool.
+    /// Holds:
+    /// - `usize`: Line number where the error occurred.
+    /// - `String`: Which types clashed.
+    TypeMismatch(usize, String),
+
+    /// Represents a `call` argument whose type does not fit its parameter.
+    /// Holds:
+    /// - `usize`: Line number where the error occurred.
+    /// - `String`: The parameter and the argument.
+    ArgType(usize, String),
+
+    /// Represents an interpolated `{expression}` that is empty or has no printed form, such as
+    /// a shape.
+    /// Holds:
+    /// - `usize`: Line number where the error occurred.
+    /// - `String`: The expression and its type.
+    Unprintable(usize, String),
+
+    /// Represents code where a statement should start, such as a second statement on the line
+    /// of a one line body.
+    /// Holds:
+    /// - `usize`: Line number where the error occurred.
+    /// - `String`: The code that starts no statement.
+    Unexpected(usize, String),
 }
 
-pub fn generr(err: ErrT, codes: &Vec<&str>) {
+pub fn generr(err: ErrT, codes: &[&str]) {
     match err {
         ErrT::InValidVarVal(line, value) => {
             let codeline = &codes[line - 1];
@@ -219,6 +333,11 @@ pub fn generr(err: ErrT, codes: &Vec<&str>) {
                 "For example, use time values like `1s`, `500ms`, `2m`, `1hr` for seconds, milliseconds, minutes, or hours"
                     .bright_cyan()
             );
+            println!(
+                " │   {}",
+                "The amount can also be a `const`, such as `wait DELAY ms`, and must not be too large to count in milliseconds."
+                    .bright_cyan()
+            );
             println!(" └─ {} {}", "Code:".bright_white(), codeline.red().italic());
         }
         ErrT::UnmatchedParen(line, code) => {
@@ -371,6 +490,433 @@ pub fn generr(err: ErrT, codes: &Vec<&str>) {
             );
             println!(" └─ {} {}", "Code:".bright_white(), codeline.red().italic());
         }
-        _ => {}
+        ErrT::DanglingElse(line, code) => {
+            let codeline = &codes[line - 1];
+            println!("{}", "ERROR: Dangling Else".bold().red());
+            println!(
+                " ├─ {} {}",
+                "Line:".bright_white(),
+                format!("{}", line).yellow().bold()
+            );
+            println!(
+                " ├─ {} {}",
+                "Cause:".bright_white(),
+                format!("`else` without a matching `if` in: `{}`", code).yellow()
+            );
+            println!(" ├─ {}", "Explanation:".bright_white());
+            println!(
+                " │   {}",
+                "An `else` or `else if` must directly follow the closing `}` of an `if` body."
+                    .bright_cyan()
+            );
+            println!(
+                " │   {}",
+                "Check that the `if` above is closed properly and nothing sits between them."
+                    .bright_cyan()
+            );
+            println!(" └─ {} {}", "Code:".bright_white(), codeline.red().italic());
+        }
+        ErrT::CmdNF(line, name) => {
+            let codeline = &codes[line - 1];
+            println!("{}", "ERROR: Undefined Command".bold().red());
+            println!(
+                " ├─ {} {}",
+                "Line:".bright_white(),
+                format!("{}", line).yellow().bold()
+            );
+            println!(
+                " ├─ {} {}",
+                "Cause:".bright_white(),
+                format!("The command `{}` has not been defined.", name).yellow()
+            );
+            println!(" ├─ {}", "Explanation:".bright_white());
+            println!(
+                " │   {}",
+                "Commands must be defined with `cmd` before they are called.".bright_cyan()
+            );
+            println!(
+                " │   {}",
+                "Check the name for typos or move the `cmd` definition above the `call`."
+                    .bright_cyan()
+            );
+            println!(" └─ {} {}", "Code:".bright_white(), codeline.red().italic());
+        }
+        ErrT::ArgCount(line, name, expected, found) => {
+            let codeline = &codes[line - 1];
+            println!("{}", "ERROR: Wrong Number Of Arguments".bold().red());
+            println!(
+                " ├─ {} {}",
+                "Line:".bright_white(),
+                format!("{}", line).yellow().bold()
+            );
+            println!(
+                " ├─ {} {}",
+                "Cause:".bright_white(),
+                format!(
+                    "The command `{}` takes {} argument(s) but {} were given.",
+                    name, expected, found
+                )
+                .yellow()
+            );
+            println!(" ├─ {}", "Explanation:".bright_white());
+            println!(
+                " │   {}",
+                "Every parameter of a command needs exactly one argument in the `call`."
+                    .bright_cyan()
+            );
+            println!(
+                " │   {}",
+                "Pass the arguments inside braces, separated by commas: `call hi {\"joy\"}`"
+                    .bright_cyan()
+            );
+            println!(" └─ {} {}", "Code:".bright_white(), codeline.red().italic());
+        }
+        ErrT::UnknownType(line, what, ty) => {
+            let codeline = &codes[line - 1];
+            println!("{}", "ERROR: Unknown Type".bold().red());
+            println!(
+                " ├─ {} {}",
+                "Line:".bright_white(),
+                format!("{}", line).yellow().bold()
+            );
+            println!(
+                " ├─ {} {}",
+                "Cause:".bright_white(),
+                if ty.is_empty() {
+                    format!("No type is given for {}.", what)
+                } else {
+                    format!("`{}` is not a known type for {}.", ty, what)
+                }
+                .yellow()
+            );
+            println!(" ├─ {}", "Explanation:".bright_white());
+            println!(
+                " │   {}",
+                "A type follows a `:`, as in `may age: int = 5` or `cmd f (name: str): int`."
+                    .bright_cyan()
+            );
+            println!(
+                " │   {}",
+                "The types are `int`, `float`, `str`, `bool`, lists such as `[int]`, maps such as"
+                    .bright_cyan()
+            );
+            println!(
+                " │   {}",
+                "`{str:int}` (keyed by `str` or `int`) and the declared shapes and enums."
+                    .bright_cyan()
+            );
+            println!(" └─ {} {}", "Code:".bright_white(), codeline.red().italic());
+        }
+        ErrT::InvalidOperand(line, cause) => {
+            let codeline = &codes[line - 1];
+            println!("{}", "ERROR: Invalid Operand".bold().red());
+            println!(
+                " ├─ {} {}",
+                "Line:".bright_white(),
+                format!("{}", line).yellow().bold()
+            );
+            println!(" ├─ {} {}", "Cause:".bright_white(), cause.yellow());
+            println!(" ├─ {}", "Explanation:".bright_white());
+            println!(
+                " │   {}",
+                "Arithmetic works on `int` and `float` values, which can be mixed freely."
+                    .bright_cyan()
+            );
+            println!(
+                " │   {}",
+                "Strings can only be joined with `+`, and only with other strings.".bright_cyan()
+            );
+            println!(
+                " │   {}",
+                "A list holds values of one type, and a map has `str` or `int` keys.".bright_cyan()
+            );
+            println!(" └─ {} {}", "Code:".bright_white(), codeline.red().italic());
+        }
+        ErrT::BadGive(line, cause) => {
+            let codeline = &codes[line - 1];
+            println!("{}", "ERROR: Invalid Give".bold().red());
+            println!(
+                " ├─ {} {}",
+                "Line:".bright_white(),
+                format!("{}", line).yellow().bold()
+            );
+            println!(" ├─ {} {}", "Cause:".bright_white(), cause.yellow());
+            println!(" ├─ {}", "Explanation:".bright_white());
+            println!(
+                " │   {}",
+                "`give` hands a value back from a command declared with a return type,"
+                    .bright_cyan()
+            );
+            println!(
+                " │   {}",
+                "for example `cmd add (a:int, b:int): int { give a }`.".bright_cyan()
+            );
+            println!(" └─ {} {}", "Code:".bright_white(), codeline.red().italic());
+        }
+        ErrT::BadFor(line, cause) => {
+            let codeline = &codes[line - 1];
+            println!("{}", "ERROR: Invalid For Loop".bold().red());
+            println!(
+                " ├─ {} {}",
+                "Line:".bright_white(),
+                format!("{}", line).yellow().bold()
+            );
+            println!(" ├─ {} {}", "Cause:".bright_white(), cause.yellow());
+            println!(" ├─ {}", "Explanation:".bright_white());
+            println!(
+                " │   {}",
+                "A `for` loop counts over an int range: `for i in 0..10 { }`.".bright_cyan()
+            );
+            println!(
+                " │   {}",
+                "Use `..=` to include the end and `step n` to count in steps of n.".bright_cyan()
+            );
+            println!(" └─ {} {}", "Code:".bright_white(), codeline.red().italic());
+        }
+        ErrT::NotInLoop(line, word) => {
+            let codeline = &codes[line - 1];
+            println!("{}", "ERROR: Not Inside A Loop".bold().red());
+            println!(
+                " ├─ {} {}",
+                "Line:".bright_white(),
+                format!("{}", line).yellow().bold()
+            );
+            println!(
+                " ├─ {} {}",
+                "Cause:".bright_white(),
+                format!("`{}` is used outside of a loop.", word).yellow()
+            );
+            println!(" ├─ {}", "Explanation:".bright_white());
+            println!(
+                " │   {}",
+                "`break` and `continue` only work inside a `while` or `for` body.".bright_cyan()
+            );
+            println!(
+                " │   {}",
+                "A command body does not count, even when the command is called from a loop."
+                    .bright_cyan()
+            );
+            println!(" └─ {} {}", "Code:".bright_white(), codeline.red().italic());
+        }
+        ErrT::BadShape(line, cause) => {
+            let codeline = &codes[line - 1];
+            println!("{}", "ERROR: Invalid Shape".bold().red());
+            println!(
+                " ├─ {} {}",
+                "Line:".bright_white(),
+                format!("{}", line).yellow().bold()
+            );
+            println!(" ├─ {} {}", "Cause:".bright_white(), cause.yellow());
+            println!(" ├─ {}", "Explanation:".bright_white());
+            println!(
+                " │   {}",
+                "A shape lists typed fields: `shape Person { name: str, age: int }`.".bright_cyan()
+            );
+            println!(
+                " │   {}",
+                "Build one by giving every field a value: `Person {name: \"joy\", age: 16}`."
+                    .bright_cyan()
+            );
+            println!(" └─ {} {}", "Code:".bright_white(), codeline.red().italic());
+        }
+        ErrT::BadEnum(line, cause) => {
+            let codeline = &codes[line - 1];
+            println!("{}", "ERROR: Invalid Enum".bold().red());
+            println!(
+                " ├─ {} {}",
+                "Line:".bright_white(),
+                format!("{}", line).yellow().bold()
+            );
+            println!(" ├─ {} {}", "Cause:".bright_white(), cause.yellow());
+            println!(" ├─ {}", "Explanation:".bright_white());
+            println!(
+                " │   {}",
+                "An enum lists the names of its variants: `enum Color { Red, Green }`."
+                    .bright_cyan()
+            );
+            println!(
+                " │   {}",
+                "A variant is written with its enum's name: `Color.Red`.".bright_cyan()
+            );
+            println!(" └─ {} {}", "Code:".bright_white(), codeline.red().italic());
+        }
+        ErrT::BadMatch(line, cause) => {
+            let codeline = &codes[line - 1];
+            println!("{}", "ERROR: Invalid Match".bold().red());
+            println!(
+                " ├─ {} {}",
+                "Line:".bright_white(),
+                format!("{}", line).yellow().bold()
+            );
+            println!(" ├─ {} {}", "Cause:".bright_white(), cause.yellow());
+            println!(" ├─ {}", "Explanation:".bright_white());
+            println!(
+                " │   {}",
+                "Each arm is `patterns => statement` or `patterns => { ... }`, with `_` last."
+                    .bright_cyan()
+            );
+            println!(
+                " │   {}",
+                "A match on an enum must cover every variant or end with a `_` arm.".bright_cyan()
+            );
+            println!(" └─ {} {}", "Code:".bright_white(), codeline.red().italic());
+        }
+        ErrT::BadUse(line, cause) => {
+            let codeline = &codes[line - 1];
+            println!("{}", "ERROR: Invalid Use".bold().red());
+            println!(
+                " ├─ {} {}",
+                "Line:".bright_white(),
+                format!("{}", line).yellow().bold()
+            );
+            println!(" ├─ {} {}", "Cause:".bright_white(), cause.yellow());
+            println!(" ├─ {}", "Explanation:".bright_white());
+            println!(
+                " │   {}",
+                "`use math.nsc` loads a file next to this one; call its commands as `math.name`."
+                    .bright_cyan()
+            );
+            println!(
+                " │   {}",
+                "Modules may only declare commands, shapes and enums, and cannot use each other in a loop."
+                    .bright_cyan()
+            );
+            println!(" └─ {} {}", "Code:".bright_white(), codeline.red().italic());
+        }
+        ErrT::BadScope(line, cause) => {
+            let codeline = &codes[line - 1];
+            println!("{}", "ERROR: Invalid Scope".bold().red());
+            println!(
+                " ├─ {} {}",
+                "Line:".bright_white(),
+                format!("{}", line).yellow().bold()
+            );
+            println!(" ├─ {} {}", "Cause:".bright_white(), cause.yellow());
+            println!(" ├─ {}", "Explanation:".bright_white());
+            println!(
+                " │   {}",
+                "Every `{ ... }` body is a scope: a name is declared once in it, may shadow one from outside, and is gone after the `}`."
+                    .bright_cyan()
+            );
+            println!(
+                " │   {}",
+                "Top level variables are globals: commands can use them but not declare their own with the same name."
+                    .bright_cyan()
+            );
+            println!(
+                " │   {}",
+                "Commands are declared at the top level of a file, outside of any other body."
+                    .bright_cyan()
+            );
+            println!(" └─ {} {}", "Code:".bright_white(), codeline.red().italic());
+        }
+        ErrT::BadConst(line, cause) => {
+            let codeline = &codes[line - 1];
+            println!("{}", "ERROR: Invalid Constant".bold().red());
+            println!(
+                " ├─ {} {}",
+                "Line:".bright_white(),
+                format!("{}", line).yellow().bold()
+            );
+            println!(" ├─ {} {}", "Cause:".bright_white(), cause.yellow());
+            println!(" ├─ {}", "Explanation:".bright_white());
+            println!(
+                " │   {}",
+                "A `const` is worked out while compiling, so its value may only use literals, other constants and operators."
+                    .bright_cyan()
+            );
+            println!(
+                " │   {}",
+                "Constants cannot be changed; use `may` for a value that changes.".bright_cyan()
+            );
+            println!(" └─ {} {}", "Code:".bright_white(), codeline.red().italic());
+        }
+        ErrT::TypeMismatch(line, cause) => {
+            let codeline = &codes[line - 1];
+            println!("{}", "ERROR: Mismatched Types".bold().red());
+            println!(
+                " ├─ {} {}",
+                "Line:".bright_white(),
+                format!("{}", line).yellow().bold()
+            );
+            println!(" ├─ {} {}", "Cause:".bright_white(), cause.yellow());
+            println!(" ├─ {}", "Explanation:".bright_white());
+            println!(
+                " │   {}",
+                "A variable keeps the type it was declared with, from its annotation or else its first value."
+                    .bright_cyan()
+            );
+            println!(
+                " │   {}",
+                "Only an int may be stored where a float is expected; convert other values with `int(...)`, `float(...)` or `str(...)`."
+                    .bright_cyan()
+            );
+            println!(" └─ {} {}", "Code:".bright_white(), codeline.red().italic());
+        }
+        ErrT::ArgType(line, cause) => {
+            let codeline = &codes[line - 1];
+            println!("{}", "ERROR: Mismatched Argument".bold().red());
+            println!(
+                " ├─ {} {}",
+                "Line:".bright_white(),
+                format!("{}", line).yellow().bold()
+            );
+            println!(" ├─ {} {}", "Cause:".bright_white(), cause.yellow());
+            println!(" ├─ {}", "Explanation:".bright_white());
+            println!(
+                " │   {}",
+                "Each argument of a `call` must have the type its parameter declares in the `cmd` header."
+                    .bright_cyan()
+            );
+            println!(" └─ {} {}", "Code:".bright_white(), codeline.red().italic());
+        }
+        ErrT::Unprintable(line, cause) => {
+            let codeline = &codes[line - 1];
+            println!("{}", "ERROR: Unprintable Value".bold().red());
+            println!(
+                " ├─ {} {}",
+                "Line:".bright_white(),
+                format!("{}", line).yellow().bold()
+            );
+            println!(" ├─ {} {}", "Cause:".bright_white(), cause.yellow());
+            println!(" ├─ {}", "Explanation:".bright_white());
+            println!(
+                " │   {}",
+                "Numbers, strings, bools, enum variants and lists or maps of those can be printed."
+                    .bright_cyan()
+            );
+            println!(
+                " │   {}",
+                "A shape has no printed form of its own, print its fields instead: `println {p.name}`"
+                    .bright_cyan()
+            );
+            println!(" └─ {} {}", "Code:".bright_white(), codeline.red().italic());
+        }
+        ErrT::Unexpected(line, code) => {
+            let codeline = &codes[line - 1];
+            println!("{}", "ERROR: Unexpected Code".bold().red());
+            println!(
+                " ├─ {} {}",
+                "Line:".bright_white(),
+                format!("{}", line).yellow().bold()
+            );
+            println!(
+                " ├─ {} {}",
+                "Cause:".bright_white(),
+                format!("`{}` does not start a statement.", code).yellow()
+            );
+            println!(" ├─ {}", "Explanation:".bright_white());
+            println!(
+                " │   {}",
+                "Each statement goes on a line of its own, and a body written on the line of its"
+                    .bright_cyan()
+            );
+            println!(
+                " │   {}",
+                "`{` holds a single statement: `if (ok) { println yes } else { println no }`"
+                    .bright_cyan()
+            );
+            println!(" └─ {} {}", "Code:".bright_white(), codeline.red().italic());
+        }
     }
 }
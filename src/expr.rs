@@ -0,0 +1,1355 @@
+use crate::{
+    err::ErrT,
+    p::{Binding, Ctx, Param, VVal},
+    p2::ValueType,
+    scope::Symbols,
+};
+use std::fmt;
+
+/// A typed expression, as found on the right of `may x = ...`, in `give` and inside `{}`
+/// in print statements.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    /// A literal value.
+    Lit(VVal),
+    /// A variable and its type.
+    Var(String, ValueType),
+    /// A binary operation and the type it produces.
+    Bin(Box<Expr>, BinOp, Box<Expr>, ValueType),
+    /// A unary operation and the type it produces.
+    Un(UnOp, Box<Expr>, ValueType),
+    /// `call name {args}` and the type the command gives.
+    Call(String, Vec<Expr>, ValueType),
+    /// `takein()`: a line read from the user.
+    Input,
+    /// A list literal such as `[1, 2, 3]` and its list type.
+    List(Vec<Expr>, ValueType),
+    /// `[value; n]`: a list of `n` copies of a value, with `n` known while parsing, and its
+    /// list type.
+    Fill(Box<Expr>, usize, ValueType),
+    /// A map literal such as `{"joy": 16}`: its key and value pairs and its map type.
+    Map(Vec<(Expr, Expr)>, ValueType),
+    /// `list[index]` or `map[key]` and the type of the value it reads.
+    Index(Box<Expr>, Box<Expr>, ValueType),
+    /// `Name {field: value, ...}`: a new shape, with its fields in declaration order.
+    Shape(String, Vec<(Param, Expr)>),
+    /// `Enum.Variant`: the enum, the variant and its position in the declaration.
+    Variant(String, String, usize),
+    /// `record.field`: the field's position in its shape and its type.
+    Field(Box<Expr>, String, usize, ValueType),
+    /// A call to a built in function such as `len(xs)` and the type it produces.
+    Builtin(Builtin, Vec<Expr>, ValueType),
+}
+
+/// Functions provided by the language rather than defined with `cmd`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Builtin {
+    /// `len(xs)`: the number of elements of a list, entries of a map or bytes of a string.
+    Len,
+    /// `push(xs, value)`: appends a value and gives back the list.
+    Push,
+    /// `pop(xs)`: removes and gives back the last element.
+    Pop,
+    /// `has(m, key)`: whether a map holds the key.
+    Has,
+    /// `remove(m, key)`: removes a key, if present, and gives back the map.
+    Remove,
+    /// `upper(s)`: a copy with ASCII letters in upper case.
+    Upper,
+    /// `lower(s)`: a copy with ASCII letters in lower case.
+    Lower,
+    /// `trim(s)`: a copy without leading and trailing whitespace.
+    Trim,
+    /// `contains(s, part)`: whether `part` occurs anywhere in the string.
+    Contains,
+    /// `starts_with(s, part)`: whether the string begins with `part`.
+    StartsWith,
+    /// `ends_with(s, part)`: whether the string ends with `part`.
+    EndsWith,
+    /// `replace(s, from, to)`: a copy with every `from` replaced by `to`.
+    Replace,
+    /// `split(s, sep)`: the pieces between each `sep`, or every character for an empty one.
+    Split,
+    /// `slice(s, from, to)`: the bytes from `from` up to, but not including, `to`.
+    Slice,
+    /// `int(x)`: a number or bool as an int, or a string parsed as one.
+    ToInt,
+    /// `float(x)`: a number as a float, or a string parsed as one.
+    ToFloat,
+    /// `str(x)`: any printable value as the text `print` would show.
+    ToStr,
+}
+
+impl Builtin {
+    pub fn from_name(name: &str) -> Option<Builtin> {
+        match name {
+            "len" => Some(Builtin::Len),
+            "push" => Some(Builtin::Push),
+            "pop" => Some(Builtin::Pop),
+            "has" => Some(Builtin::Has),
+            "remove" => Some(Builtin::Remove),
+            "upper" => Some(Builtin::Upper),
+            "lower" => Some(Builtin::Lower),
+            "trim" => Some(Builtin::Trim),
+            "contains" => Some(Builtin::Contains),
+            "starts_with" => Some(Builtin::StartsWith),
+            "ends_with" => Some(Builtin::EndsWith),
+            "replace" => Some(Builtin::Replace),
+            "split" => Some(Builtin::Split),
+            "slice" => Some(Builtin::Slice),
+            "int" => Some(Builtin::ToInt),
+            "float" => Some(Builtin::ToFloat),
+            "str" => Some(Builtin::ToStr),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Builtin::Len => "len",
+            Builtin::Push => "push",
+            Builtin::Pop => "pop",
+            Builtin::Has => "has",
+            Builtin::Remove => "remove",
+            Builtin::Upper => "upper",
+            Builtin::Lower => "lower",
+            Builtin::Trim => "trim",
+            Builtin::Contains => "contains",
+            Builtin::StartsWith => "starts_with",
+            Builtin::EndsWith => "ends_with",
+            Builtin::Replace => "replace",
+            Builtin::Split => "split",
+            Builtin::Slice => "slice",
+            Builtin::ToInt => "int",
+            Builtin::ToFloat => "float",
+            Builtin::ToStr => "str",
+        }
+    }
+
+    /// Whether this works on a string, whose other arguments are all strings or, for
+    /// `slice`, ints.
+    pub fn on_str(&self) -> bool {
+        !matches!(
+            self,
+            Builtin::Len
+                | Builtin::Push
+                | Builtin::Pop
+                | Builtin::Has
+                | Builtin::Remove
+                | Builtin::ToInt
+                | Builtin::ToFloat
+                | Builtin::ToStr
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Eq,
+    Ne,
+    Lt,
+    Gt,
+    Le,
+    Ge,
+    And,
+    Or,
+}
+
+/// Binary operators from the loosest to the tightest binding, as in C.
+const LEVELS: &[&[BinOp]] = &[
+    &[BinOp::Or],
+    &[BinOp::And],
+    &[BinOp::Eq, BinOp::Ne],
+    &[BinOp::Lt, BinOp::Gt, BinOp::Le, BinOp::Ge],
+    &[BinOp::Add, BinOp::Sub],
+    &[BinOp::Mul, BinOp::Div, BinOp::Mod],
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnOp {
+    Neg,
+    Not,
+}
+
+impl Expr {
+    /// The type this expression evaluates to.
+    pub fn ty(&self) -> ValueType {
+        match self {
+            Expr::Lit(VVal::Int(_)) => ValueType::Int,
+            Expr::Lit(VVal::F(_)) => ValueType::Float,
+            Expr::Lit(VVal::Str(_)) | Expr::Input => ValueType::Str,
+            Expr::Shape(name, _) => ValueType::Shape(name.clone()),
+            Expr::Variant(name, _, _) => ValueType::Enum(name.clone()),
+            Expr::Lit(VVal::Bool(_)) => ValueType::Bool,
+            Expr::Lit(VVal::List(_) | VVal::Map(_) | VVal::Shape(_) | VVal::Enum(..)) => {
+                unreachable!("lists, maps, shapes and enums are built by their own Expr variants")
+            }
+            Expr::Var(_, t)
+            | Expr::Bin(_, _, _, t)
+            | Expr::Un(_, _, t)
+            | Expr::Call(_, _, t)
+            | Expr::List(_, t)
+            | Expr::Fill(_, _, t)
+            | Expr::Map(_, t)
+            | Expr::Index(_, _, t)
+            | Expr::Field(_, _, _, t)
+            | Expr::Builtin(_, _, t) => t.clone(),
+        }
+    }
+
+    /// Whether the variable `name` is read anywhere in this expression.
+    pub fn uses(&self, name: &str) -> bool {
+        match self {
+            Expr::Var(v, _) => v == name,
+            Expr::Lit(_) | Expr::Input | Expr::Variant(..) => false,
+            Expr::Bin(l, _, r, _) | Expr::Index(l, r, _) => l.uses(name) || r.uses(name),
+            Expr::Un(_, e, _) | Expr::Field(e, ..) | Expr::Fill(e, ..) => e.uses(name),
+            Expr::Call(_, args, _) | Expr::List(args, _) | Expr::Builtin(_, args, _) => {
+                args.iter().any(|a| a.uses(name))
+            }
+            Expr::Map(pairs, _) => pairs.iter().any(|(k, v)| k.uses(name) || v.uses(name)),
+            Expr::Shape(_, fields) => fields.iter().any(|(_, e)| e.uses(name)),
+        }
+    }
+
+    /// The expressions directly inside this one, in the order the interpreter works them out.
+    pub fn children(&self) -> Vec<&Expr> {
+        match self {
+            Expr::Lit(_) | Expr::Var(..) | Expr::Input | Expr::Variant(..) => Vec::new(),
+            Expr::Bin(l, _, r, _) | Expr::Index(l, r, _) => vec![l, r],
+            Expr::Un(_, e, _) | Expr::Field(e, ..) | Expr::Fill(e, ..) => vec![e],
+            Expr::Call(_, args, _) | Expr::List(args, _) | Expr::Builtin(_, args, _) => {
+                args.iter().collect()
+            }
+            Expr::Map(pairs, _) => pairs.iter().flat_map(|(k, v)| [k, v]).collect(),
+            Expr::Shape(_, fields) => fields.iter().map(|(_, e)| e).collect(),
+        }
+    }
+
+    /// Like [`Expr::children`], but mutable.
+    pub fn children_mut(&mut self) -> Vec<&mut Expr> {
+        match self {
+            Expr::Lit(_) | Expr::Var(..) | Expr::Input | Expr::Variant(..) => Vec::new(),
+            Expr::Bin(l, _, r, _) | Expr::Index(l, r, _) => vec![l, r],
+            Expr::Un(_, e, _) | Expr::Field(e, ..) | Expr::Fill(e, ..) => vec![e],
+            Expr::Call(_, args, _) | Expr::List(args, _) | Expr::Builtin(_, args, _) => {
+                args.iter_mut().collect()
+            }
+            Expr::Map(pairs, _) => pairs.iter_mut().flat_map(|(k, v)| [k, v]).collect(),
+            Expr::Shape(_, fields) => fields.iter_mut().map(|(_, e)| e).collect(),
+        }
+    }
+
+    /// Works out an expression made of literals, constants and operators while parsing, as
+    /// `const` needs, giving back a literal or enum variant, or why it cannot be done.
+    pub fn fold(&self) -> Result<Expr, String> {
+        match (self.const_val()?, self.ty()) {
+            (VVal::Enum(idx, variant), ValueType::Enum(name)) => {
+                Ok(Expr::Variant(name, variant, idx))
+            }
+            (v, _) => Ok(Expr::Lit(v)),
+        }
+    }
+
+    fn const_val(&self) -> Result<VVal, String> {
+        match self {
+            Expr::Lit(v) => Ok(v.clone()),
+            Expr::Variant(_, variant, idx) => Ok(VVal::Enum(*idx, variant.clone())),
+            Expr::Un(UnOp::Neg, e, _) => match e.const_val()? {
+                VVal::Int(i) => i
+                    .checked_neg()
+                    .map(VVal::Int)
+                    .ok_or_else(|| format!("`{}` does not fit in an int", self)),
+                VVal::F(x) => Ok(VVal::F(-x)),
+                _ => unreachable!("negating a value that is not a number"),
+            },
+            Expr::Un(UnOp::Not, e, _) => match e.const_val()? {
+                VVal::Bool(b) => Ok(VVal::Bool(!b)),
+                _ => unreachable!("`!` on a value that is not a bool"),
+            },
+            Expr::Bin(l, op, r, ty) => {
+                let (a, b) = (l.const_val()?, r.const_val()?);
+                fold_bin(*op, a, b, ty).ok_or_else(|| match op {
+                    BinOp::Div | BinOp::Mod => format!("`{}` divides by zero", self),
+                    _ => format!("`{}` does not fit in an int", self),
+                })
+            }
+            _ => Err(format!(
+                "`{}` is not made of literals, constants and operators",
+                self
+            )),
+        }
+    }
+}
+
+/// Applies an operator to two known values of the types the parser accepted, or `None` if
+/// the result would overflow an int or divide by zero. Mirrors what the program itself does.
+fn fold_bin(op: BinOp, a: VVal, b: VVal, ty: &ValueType) -> Option<VVal> {
+    use std::cmp::Ordering;
+    if op.is_comparison() {
+        let ord = match (&a, &b) {
+            (VVal::Str(x), VVal::Str(y)) => x.as_bytes().cmp(y.as_bytes()),
+            (VVal::Int(x), VVal::Int(y)) => x.cmp(y),
+            (VVal::Bool(x), VVal::Bool(y)) => x.cmp(y),
+            (VVal::Enum(x, _), VVal::Enum(y, _)) => x.cmp(y),
+            // NaN compares unequal to everything
+            _ => match num(&a).partial_cmp(&num(&b)) {
+                Some(ord) => ord,
+                None => return Some(VVal::Bool(op == BinOp::Ne)),
+            },
+        };
+        return Some(VVal::Bool(match op {
+            BinOp::Eq => ord == Ordering::Equal,
+            BinOp::Ne => ord != Ordering::Equal,
+            BinOp::Lt => ord == Ordering::Less,
+            BinOp::Gt => ord == Ordering::Greater,
+            BinOp::Le => ord != Ordering::Greater,
+            _ => ord != Ordering::Less,
+        }));
+    }
+    Some(match (op, a, b) {
+        (BinOp::And, VVal::Bool(x), VVal::Bool(y)) => VVal::Bool(x && y),
+        (BinOp::Or, VVal::Bool(x), VVal::Bool(y)) => VVal::Bool(x || y),
+        (_, VVal::Str(x), VVal::Str(y)) => VVal::Str(x + &y),
+        (_, VVal::Int(x), VVal::Int(y)) if *ty == ValueType::Int => VVal::Int(match op {
+            BinOp::Add => x.checked_add(y)?,
+            BinOp::Sub => x.checked_sub(y)?,
+            BinOp::Mul => x.checked_mul(y)?,
+            BinOp::Div => x.checked_div(y)?,
+            BinOp::Mod => x.checked_rem(y)?,
+            _ => unreachable!("`{}` is not arithmetic", op.symbol()),
+        }),
+        (_, a, b) => {
+            let (x, y) = (num(&a) as f32, num(&b) as f32);
+            VVal::F(match op {
+                BinOp::Add => x + y,
+                BinOp::Sub => x - y,
+                BinOp::Mul => x * y,
+                BinOp::Div => x / y,
+                _ => unreachable!("`{}` is not arithmetic", op.symbol()),
+            })
+        }
+    })
+}
+
+fn num(v: &VVal) -> f64 {
+    match v {
+        VVal::Int(i) => *i as f64,
+        VVal::F(x) => *x as f64,
+        _ => unreachable!("a number was expected"),
+    }
+}
+
+impl BinOp {
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            BinOp::Add => "+",
+            BinOp::Sub => "-",
+            BinOp::Mul => "*",
+            BinOp::Div => "/",
+            BinOp::Mod => "%",
+            BinOp::Eq => "==",
+            BinOp::Ne => "!=",
+            BinOp::Lt => "<",
+            BinOp::Gt => ">",
+            BinOp::Le => "<=",
+            BinOp::Ge => ">=",
+            BinOp::And => "&&",
+            BinOp::Or => "||",
+        }
+    }
+
+    /// Whether the operator compares its operands rather than computing a value.
+    pub fn is_comparison(&self) -> bool {
+        matches!(
+            self,
+            BinOp::Eq | BinOp::Ne | BinOp::Lt | BinOp::Gt | BinOp::Le | BinOp::Ge
+        )
+    }
+}
+
+impl fmt::Display for Expr {
+    /// Renders the expression back as Neit source.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expr::Lit(VVal::Str(s)) => write!(f, "{:?}", s),
+            Expr::Lit(VVal::Int(i)) => write!(f, "{}", i),
+            Expr::Lit(VVal::F(x)) => write!(f, "{:?}", x),
+            Expr::Lit(VVal::Bool(b)) => write!(f, "{}", b),
+            Expr::Lit(VVal::List(_) | VVal::Map(_) | VVal::Shape(_) | VVal::Enum(..)) => {
+                unreachable!("lists, maps, shapes and enums are built by their own Expr variants")
+            }
+            Expr::Var(n, _) => write!(f, "{}", n),
+            Expr::Bin(l, op, r, _) => write!(f, "({} {} {})", l, op.symbol(), r),
+            Expr::Un(UnOp::Neg, e, _) => write!(f, "-{}", e),
+            Expr::Un(UnOp::Not, e, _) => write!(f, "!{}", e),
+            Expr::Call(n, args, _) => {
+                let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+                write!(f, "call {} {{{}}}", n, args.join(", "))
+            }
+            Expr::Input => write!(f, "takein()"),
+            Expr::List(items, _) => {
+                let items: Vec<String> = items.iter().map(|a| a.to_string()).collect();
+                write!(f, "[{}]", items.join(", "))
+            }
+            Expr::Fill(value, n, _) => write!(f, "[{}; {}]", value, n),
+            Expr::Map(pairs, _) => {
+                let pairs: Vec<String> =
+                    pairs.iter().map(|(k, v)| format!("{}: {}", k, v)).collect();
+                write!(f, "{{{}}}", pairs.join(", "))
+            }
+            Expr::Shape(name, fields) => {
+                let fields: Vec<String> = fields
+                    .iter()
+                    .map(|(f, v)| format!("{}: {}", f.name, v))
+                    .collect();
+                write!(f, "{} {{{}}}", name, fields.join(", "))
+            }
+            Expr::Field(e, field, _, _) => write!(f, "{}.{}", e, field),
+            Expr::Variant(name, variant, _) => write!(f, "{}.{}", name, variant),
+            Expr::Index(l, i, _) => write!(f, "{}[{}]", l, i),
+            Expr::Builtin(b @ (Builtin::ToInt | Builtin::ToFloat), args, _)
+                if args[..] == [Expr::Input] =>
+            {
+                write!(f, "takein_{}()", b.name())
+            }
+            Expr::Builtin(b, args, _) => {
+                let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+                write!(f, "{}({})", b.name(), args.join(", "))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum ETok {
+    /// Read wider than an int, so that `-2147483648` can be told apart from a literal that
+    /// does not fit.
+    Int(i64),
+    Float(f32),
+    Str(String),
+    Ident(String),
+    Op(&'static str),
+}
+
+/// Parses an expression such as `a + b * 2`, resolving variable and call types.
+///
+/// Errors are pushed to `errors` and `None` is returned when the expression is unusable.
+pub fn parse_expr(
+    src: &str,
+    ln: usize,
+    vars: &Symbols<Binding>,
+    ctx: &Ctx,
+    errors: &mut Vec<ErrT>,
+) -> Option<Expr> {
+    parse_expr_as(src, None, ln, vars, ctx, errors)
+}
+
+/// Like [`parse_expr`], for a place that needs a value of type `expect`, which gives an empty
+/// list `[]` its element type.
+pub fn parse_expr_as(
+    src: &str,
+    expect: Option<&ValueType>,
+    ln: usize,
+    vars: &Symbols<Binding>,
+    ctx: &Ctx,
+    errors: &mut Vec<ErrT>,
+) -> Option<Expr> {
+    let mut p = Parser::new(src, ln, vars, ctx, errors)?;
+    let e = p.expr_as(expect.cloned())?;
+    p.finish()?;
+    Some(e)
+}
+
+/// Parses the text after a `call` statement, e.g. `add {1, 2}`, giving the command's link, the
+/// arguments and the type of the value it gives, if any.
+pub fn parse_call_stmt(
+    src: &str,
+    ln: usize,
+    vars: &Symbols<Binding>,
+    ctx: &Ctx,
+    errors: &mut Vec<ErrT>,
+) -> Option<(String, Vec<Expr>, Option<ValueType>)> {
+    let mut p = Parser::new(src, ln, vars, ctx, errors)?;
+    let (name, args) = p.call()?;
+    p.finish()?;
+    let sig = &ctx.cmds[&name];
+    Some((sig.link.clone(), args, sig.ret.clone()))
+}
+
+struct Parser<'a> {
+    src: &'a str,
+    toks: Vec<ETok>,
+    pos: usize,
+    ln: usize,
+    vars: &'a Symbols<Binding>,
+    ctx: &'a Ctx,
+    errors: &'a mut Vec<ErrT>,
+    /// The type the expression being parsed should have, if the context knows it.
+    expect: Option<ValueType>,
+}
+
+impl<'a> Parser<'a> {
+    fn new(
+        src: &'a str,
+        ln: usize,
+        vars: &'a Symbols<Binding>,
+        ctx: &'a Ctx,
+        errors: &'a mut Vec<ErrT>,
+    ) -> Option<Parser<'a>> {
+        let toks = match etokens(src) {
+            Ok(toks) => toks,
+            Err(e) => {
+                errors.push(e.into_err(ln));
+                return None;
+            }
+        };
+        Some(Parser {
+            src,
+            toks,
+            pos: 0,
+            ln,
+            vars,
+            ctx,
+            errors,
+            expect: None,
+        })
+    }
+
+    fn peek(&self) -> Option<&ETok> {
+        self.toks.get(self.pos)
+    }
+
+    fn eat(&mut self, op: &'static str) -> bool {
+        if self.peek() == Some(&ETok::Op(op)) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Records a malformed expression.
+    fn invalid(&mut self) -> Option<Expr> {
+        self.errors
+            .push(ErrT::InValidVarVal(self.ln, self.src.trim().to_string()));
+        None
+    }
+
+    fn finish(&mut self) -> Option<()> {
+        if self.pos == self.toks.len() {
+            Some(())
+        } else {
+            self.invalid();
+            None
+        }
+    }
+
+    fn expr(&mut self) -> Option<Expr> {
+        self.expr_as(None)
+    }
+
+    fn expr_as(&mut self, expect: Option<ValueType>) -> Option<Expr> {
+        let outer = std::mem::replace(&mut self.expect, expect);
+        let e = self.level(0);
+        self.expect = outer;
+        e
+    }
+
+    fn operand_err(&mut self, msg: String) -> Option<Expr> {
+        self.errors.push(ErrT::InvalidOperand(self.ln, msg));
+        None
+    }
+
+    /// Parses the operators of one precedence level, left associatively.
+    fn level(&mut self, n: usize) -> Option<Expr> {
+        let Some(ops) = LEVELS.get(n) else {
+            return self.unary();
+        };
+        let mut left = self.level(n + 1)?;
+        loop {
+            let op = match self.peek() {
+                Some(ETok::Op(s)) => match ops.iter().find(|op| op.symbol() == *s) {
+                    Some(op) => *op,
+                    None => return Some(left),
+                },
+                _ => return Some(left),
+            };
+            self.pos += 1;
+            let right = self.level(n + 1)?;
+            left = self.binary(left, op, right)?;
+        }
+    }
+
+    fn unary(&mut self) -> Option<Expr> {
+        // The smallest int is only a literal with its sign, as 2147483648 alone does not fit
+        if self.peek() == Some(&ETok::Op("-"))
+            && self.toks.get(self.pos + 1) == Some(&ETok::Int(i32::MIN.unsigned_abs().into()))
+        {
+            self.pos += 2;
+            return Some(Expr::Lit(VVal::Int(i32::MIN)));
+        }
+        let op = if self.eat("-") {
+            UnOp::Neg
+        } else if self.eat("!") {
+            UnOp::Not
+        } else {
+            return self.postfix();
+        };
+        let e = self.unary()?;
+        match (op, e.ty()) {
+            (UnOp::Neg, ty @ (ValueType::Int | ValueType::Float)) => {
+                Some(Expr::Un(op, Box::new(e), ty))
+            }
+            (UnOp::Not, ValueType::Bool) => Some(Expr::Un(op, Box::new(e), ValueType::Bool)),
+            (UnOp::Neg, t) => {
+                self.errors.push(ErrT::InvalidOperand(
+                    self.ln,
+                    format!("Cannot negate `{}` of type {}", e, t),
+                ));
+                None
+            }
+            (UnOp::Not, t) => {
+                self.errors.push(ErrT::InvalidOperand(
+                    self.ln,
+                    format!("`!` needs a comparison, but `{}` is of type {}", e, t),
+                ));
+                None
+            }
+        }
+    }
+
+    /// Parses a primary expression followed by any number of `[index]`, `[key]` or `.field`
+    /// suffixes.
+    fn postfix(&mut self) -> Option<Expr> {
+        let mut e = self.primary()?;
+        loop {
+            if self.eat(".") {
+                e = self.field(e)?;
+                continue;
+            }
+            if !self.eat("[") {
+                break;
+            }
+            let idx = self.expr()?;
+            if !self.eat("]") {
+                return self.invalid();
+            }
+            let ty = match e.ty() {
+                ValueType::List(_) | ValueType::Str if idx.ty() != ValueType::Int => {
+                    return self.operand_err(format!("The index `{}` must be an int", idx));
+                }
+                ValueType::List(elem) => *elem,
+                // Indexing a string gives its byte at that position as a string of its own
+                ValueType::Str => ValueType::Str,
+                ValueType::Map(key, _) if *key != idx.ty() => {
+                    let msg = format!("The key `{}` must be of type {}", idx, key);
+                    return self.operand_err(msg);
+                }
+                ValueType::Map(_, val) => *val,
+                ty => {
+                    return self.operand_err(format!("`{}` of type {} cannot be indexed", e, ty));
+                }
+            };
+            e = Expr::Index(Box::new(e), Box::new(idx), ty);
+        }
+        Some(e)
+    }
+
+    fn primary(&mut self) -> Option<Expr> {
+        let tok = match self.peek() {
+            Some(tok) => tok.clone(),
+            None => return self.invalid(),
+        };
+        self.pos += 1;
+        match tok {
+            ETok::Int(i) => match i32::try_from(i) {
+                Ok(i) => Some(Expr::Lit(VVal::Int(i))),
+                Err(_) => {
+                    self.errors
+                        .push(ErrT::InValidVarVal(self.ln, i.to_string()));
+                    None
+                }
+            },
+            ETok::Float(f) => Some(Expr::Lit(VVal::F(f))),
+            ETok::Str(s) => Some(Expr::Lit(VVal::Str(s))),
+            ETok::Op("(") => {
+                let e = self.expr()?;
+                if !self.eat(")") {
+                    self.errors
+                        .push(ErrT::UnmatchedParen(self.ln, self.src.trim().to_string()));
+                    return None;
+                }
+                Some(e)
+            }
+            ETok::Ident(name) if name == "takein" && self.peek() == Some(&ETok::Op("(")) => {
+                self.pos += 1;
+                if !self.eat(")") {
+                    return self.invalid();
+                }
+                Some(Expr::Input)
+            }
+            // Typed input reads a line and converts it, failing at runtime if it doesn't parse
+            ETok::Ident(name)
+                if (name == "takein_int" || name == "takein_float")
+                    && self.peek() == Some(&ETok::Op("(")) =>
+            {
+                self.pos += 1;
+                if !self.eat(")") {
+                    return self.invalid();
+                }
+                let (b, ty) = match name.as_str() {
+                    "takein_int" => (Builtin::ToInt, ValueType::Int),
+                    _ => (Builtin::ToFloat, ValueType::Float),
+                };
+                Some(Expr::Builtin(b, vec![Expr::Input], ty))
+            }
+            ETok::Op("[") => self.list(),
+            ETok::Op("{") => self.map(),
+            ETok::Ident(name)
+                if self.peek() == Some(&ETok::Op("(")) && Builtin::from_name(&name).is_some() =>
+            {
+                self.pos += 1;
+                self.builtin(Builtin::from_name(&name)?)
+            }
+            ETok::Ident(name)
+                if self.peek() == Some(&ETok::Op("{")) && self.ctx.shapes.contains_key(&name) =>
+            {
+                self.pos += 1;
+                self.shape(name)
+            }
+            ETok::Ident(name)
+                if self.peek() == Some(&ETok::Op(".")) && self.ctx.enums.contains_key(&name) =>
+            {
+                self.pos += 1;
+                let Some(ETok::Ident(variant)) = self.peek().cloned() else {
+                    return self.invalid();
+                };
+                self.pos += 1;
+                match self.ctx.enums[&name].iter().position(|v| *v == variant) {
+                    Some(idx) => Some(Expr::Variant(name, variant, idx)),
+                    None => {
+                        self.errors.push(ErrT::BadEnum(
+                            self.ln,
+                            format!("The enum {} has no variant `{}`", name, variant),
+                        ));
+                        None
+                    }
+                }
+            }
+            ETok::Ident(name) if name == "true" || name == "false" => {
+                Some(Expr::Lit(VVal::Bool(name == "true")))
+            }
+            ETok::Ident(name) if name == "call" => {
+                let (name, args) = self.call()?;
+                match self.ctx.cmds.get(&name).map(|sig| sig.ret.clone()) {
+                    Some(Some(ty)) => Some(Expr::Call(self.ctx.cmds[&name].link.clone(), args, ty)),
+                    Some(None) => {
+                        self.errors.push(ErrT::InvalidOperand(
+                            self.ln,
+                            format!("The command `{}` does not give a value", name),
+                        ));
+                        None
+                    }
+                    // Already reported by `call`
+                    None => None,
+                }
+            }
+            ETok::Ident(name) => match self.vars.get(&name) {
+                // Constants are replaced by their value
+                Some(Binding {
+                    value: Some(value), ..
+                }) => Some(value.clone()),
+                Some(b) => Some(Expr::Var(name, b.ty.clone())),
+                None => {
+                    self.errors.push(ErrT::VNF(self.ln, name));
+                    None
+                }
+            },
+            ETok::Op(_) => {
+                self.pos -= 1;
+                self.invalid()
+            }
+        }
+    }
+
+    /// Parses `name {arg, ...}` or `module.name {arg, ...}` (the `call` keyword already
+    /// consumed), checking the signature.
+    fn call(&mut self) -> Option<(String, Vec<Expr>)> {
+        let mut name = match self.peek() {
+            Some(ETok::Ident(name)) if name != "call" => name.clone(),
+            _ => {
+                self.invalid();
+                return None;
+            }
+        };
+        self.pos += 1;
+        if self.eat(".") {
+            match self.peek() {
+                Some(ETok::Ident(cmd)) => name = format!("{}.{}", name, cmd),
+                _ => {
+                    self.invalid();
+                    return None;
+                }
+            }
+            self.pos += 1;
+        }
+
+        let params = self
+            .ctx
+            .cmds
+            .get(&name)
+            .map(|sig| sig.params.clone())
+            .unwrap_or_default();
+        let mut args = Vec::new();
+        if self.eat("{") && !self.eat("}") {
+            loop {
+                let expect = params.get(args.len()).map(|p| p.ty.clone());
+                args.push(self.expr_as(expect)?);
+                if self.eat("}") {
+                    break;
+                }
+                if !self.eat(",") {
+                    self.invalid();
+                    return None;
+                }
+            }
+        }
+
+        match self.ctx.cmds.get(&name) {
+            None => {
+                self.errors.push(ErrT::CmdNF(self.ln, name));
+                return None;
+            }
+            Some(sig) if sig.params.len() != args.len() => {
+                self.errors
+                    .push(ErrT::ArgCount(self.ln, name, sig.params.len(), args.len()));
+                return None;
+            }
+            Some(_) => {}
+        }
+        Some((name, args))
+    }
+
+    /// Parses the rest of a list literal after its `[`.
+    fn list(&mut self) -> Option<Expr> {
+        let expect = match &self.expect {
+            Some(ValueType::List(elem)) => Some((**elem).clone()),
+            _ => None,
+        };
+        let mut items = Vec::new();
+        if !self.eat("]") {
+            loop {
+                items.push(self.expr_as(expect.clone())?);
+                if items.len() == 1 && self.eat(";") {
+                    return self.fill(items.remove(0), expect);
+                }
+                if self.eat("]") {
+                    break;
+                }
+                if !self.eat(",") {
+                    return self.invalid();
+                }
+            }
+        }
+
+        // The element type comes from the context, or else from the elements themselves
+        let elem =
+            match expect {
+                Some(elem) => elem,
+                None if items.iter().any(|e| e.ty() == ValueType::Float) => ValueType::Float,
+                None => match items.first() {
+                    Some(first) => first.ty(),
+                    None => return self.operand_err(
+                        "The element type of an empty list is unknown, declare it with a type: \
+                         `may xs: [int] = []`"
+                            .to_string(),
+                    ),
+                },
+            };
+        if let Some(bad) = items.iter().find(|e| !elem.accepts(&e.ty())) {
+            let msg = format!(
+                "A list of {} cannot hold `{}` of type {}",
+                elem,
+                bad,
+                bad.ty()
+            );
+            return self.operand_err(msg);
+        }
+        Some(Expr::List(items, ValueType::List(Box::new(elem))))
+    }
+
+    /// Parses the size of `[value; n]` (the value and `;` already consumed), which has to be
+    /// known while parsing.
+    fn fill(&mut self, value: Expr, expect: Option<ValueType>) -> Option<Expr> {
+        let size = self.expr()?;
+        if !self.eat("]") {
+            return self.invalid();
+        }
+        let n = match size.fold() {
+            Ok(Expr::Lit(VVal::Int(n))) if n >= 0 => n as usize,
+            Ok(other) => {
+                return self.const_err(format!(
+                    "The size of a list must be a whole number of at least 0, not `{}`",
+                    other
+                ))
+            }
+            Err(why) => {
+                return self.const_err(format!(
+                    "The size of a list must be known while compiling, but {}",
+                    why
+                ))
+            }
+        };
+        let elem = expect.unwrap_or_else(|| value.ty());
+        if !elem.accepts(&value.ty()) {
+            let msg = format!(
+                "A list of {} cannot hold `{}` of type {}",
+                elem,
+                value,
+                value.ty()
+            );
+            return self.operand_err(msg);
+        }
+        // Every element would be the very same list, map or shape
+        if matches!(
+            elem,
+            ValueType::List(_) | ValueType::Map(..) | ValueType::Shape(_)
+        ) {
+            let msg = format!(
+                "`[{}; {}]` would share one {} between every element, write the elements out or push them in a loop",
+                value, n, elem
+            );
+            return self.operand_err(msg);
+        }
+        Some(Expr::Fill(
+            Box::new(value),
+            n,
+            ValueType::List(Box::new(elem)),
+        ))
+    }
+
+    fn const_err(&mut self, msg: String) -> Option<Expr> {
+        self.errors.push(ErrT::BadConst(self.ln, msg));
+        None
+    }
+
+    /// Parses the field name after the `.` of `record.field`.
+    fn field(&mut self, e: Expr) -> Option<Expr> {
+        let Some(ETok::Ident(field)) = self.peek().cloned() else {
+            return self.invalid();
+        };
+        self.pos += 1;
+        let ValueType::Shape(shape) = e.ty() else {
+            return self.operand_err(format!("`{}` of type {} has no fields", e, e.ty()));
+        };
+        let fields = &self.ctx.shapes[&shape];
+        let Some(pos) = fields.iter().position(|f| f.name == field) else {
+            self.errors.push(ErrT::BadShape(
+                self.ln,
+                format!("The shape {} has no field `{}`", shape, field),
+            ));
+            return None;
+        };
+        let ty = fields[pos].ty.clone();
+        Some(Expr::Field(Box::new(e), field, pos, ty))
+    }
+
+    /// Parses the `{field: value, ...}` that follows a shape's name.
+    fn shape(&mut self, name: String) -> Option<Expr> {
+        let fields = self.ctx.shapes[&name].clone();
+        let mut given: Vec<(Param, Expr)> = Vec::new();
+        if !self.eat("}") {
+            loop {
+                let Some(ETok::Ident(field)) = self.peek().cloned() else {
+                    return self.invalid();
+                };
+                self.pos += 1;
+                if !self.eat(":") {
+                    return self.invalid();
+                }
+                let Some(decl) = fields.iter().find(|f| f.name == field) else {
+                    self.errors.push(ErrT::BadShape(
+                        self.ln,
+                        format!("The shape {} has no field `{}`", name, field),
+                    ));
+                    return None;
+                };
+                let value = self.expr_as(Some(decl.ty.clone()))?;
+                if !decl.ty.accepts(&value.ty()) {
+                    self.errors.push(ErrT::BadShape(
+                        self.ln,
+                        format!(
+                            "The field `{}` is of type {}, but `{}` is of type {}",
+                            field,
+                            decl.ty,
+                            value,
+                            value.ty()
+                        ),
+                    ));
+                    return None;
+                }
+                if given.iter().any(|(f, _)| f.name == field) {
+                    self.errors.push(ErrT::BadShape(
+                        self.ln,
+                        format!("The field `{}` is given twice", field),
+                    ));
+                    return None;
+                }
+                given.push((decl.clone(), value));
+                if self.eat("}") {
+                    break;
+                }
+                if !self.eat(",") {
+                    return self.invalid();
+                }
+            }
+        }
+
+        // Stored in declaration order, so both backends lay the values out the same way
+        let mut values = Vec::new();
+        for decl in &fields {
+            match given.iter().position(|(f, _)| f.name == decl.name) {
+                Some(i) => values.push(given.remove(i)),
+                None => {
+                    self.errors.push(ErrT::BadShape(
+                        self.ln,
+                        format!("The field `{}` of {} is not given", decl.name, name),
+                    ));
+                    return None;
+                }
+            }
+        }
+        Some(Expr::Shape(name, values))
+    }
+
+    /// Parses the rest of a map literal after its `{`.
+    fn map(&mut self) -> Option<Expr> {
+        let (expect_key, expect_val) = match &self.expect {
+            Some(ValueType::Map(k, v)) => (Some((**k).clone()), Some((**v).clone())),
+            _ => (None, None),
+        };
+        let mut pairs = Vec::new();
+        if !self.eat("}") {
+            loop {
+                let key = self.expr_as(expect_key.clone())?;
+                if !self.eat(":") {
+                    return self.invalid();
+                }
+                let val = self.expr_as(expect_val.clone())?;
+                pairs.push((key, val));
+                if self.eat("}") {
+                    break;
+                }
+                if !self.eat(",") {
+                    return self.invalid();
+                }
+            }
+        }
+
+        // As with lists, the types come from the context or else from the entries
+        let (key, val) = match (expect_key, expect_val) {
+            (Some(key), Some(val)) => (key, val),
+            _ => match pairs.first() {
+                Some((k, _)) if pairs.iter().any(|(_, v)| v.ty() == ValueType::Float) => {
+                    (k.ty(), ValueType::Float)
+                }
+                Some((k, v)) => (k.ty(), v.ty()),
+                None => {
+                    return self.operand_err(
+                        "The types of an empty map are unknown, declare them with a type: \
+                         `may ages: {str: int} = {}`"
+                            .to_string(),
+                    )
+                }
+            },
+        };
+        if !ValueType::is_key(&key) {
+            return self.operand_err(format!("Map keys must be of type str or int, not {}", key));
+        }
+        if let Some((k, _)) = pairs.iter().find(|(k, _)| k.ty() != key) {
+            let msg = format!("The key `{}` must be of type {}", k, key);
+            return self.operand_err(msg);
+        }
+        if let Some((_, v)) = pairs.iter().find(|(_, v)| !val.accepts(&v.ty())) {
+            let msg = format!(
+                "A map of {} values cannot hold `{}` of type {}",
+                val,
+                v,
+                v.ty()
+            );
+            return self.operand_err(msg);
+        }
+        Some(Expr::Map(
+            pairs,
+            ValueType::Map(Box::new(key), Box::new(val)),
+        ))
+    }
+
+    /// Parses the arguments of a built in function after its `(` and type checks the call.
+    fn builtin(&mut self, b: Builtin) -> Option<Expr> {
+        let mut args: Vec<Expr> = Vec::new();
+        if !self.eat(")") {
+            loop {
+                // The value pushed onto a list is expected to have the list's element type
+                let expect = match (b, args.first().map(|a| a.ty())) {
+                    (Builtin::Push, Some(ValueType::List(elem))) => Some(*elem),
+                    (Builtin::Has | Builtin::Remove, Some(ValueType::Map(key, _))) => Some(*key),
+                    _ => None,
+                };
+                args.push(self.expr_as(expect)?);
+                if self.eat(")") {
+                    break;
+                }
+                if !self.eat(",") {
+                    return self.invalid();
+                }
+            }
+        }
+
+        let arity = match b {
+            Builtin::Len
+            | Builtin::Pop
+            | Builtin::Upper
+            | Builtin::Lower
+            | Builtin::Trim
+            | Builtin::ToInt
+            | Builtin::ToFloat
+            | Builtin::ToStr => 1,
+            Builtin::Push
+            | Builtin::Has
+            | Builtin::Remove
+            | Builtin::Contains
+            | Builtin::StartsWith
+            | Builtin::EndsWith
+            | Builtin::Split => 2,
+            Builtin::Replace | Builtin::Slice => 3,
+        };
+        if args.len() != arity {
+            self.errors.push(ErrT::ArgCount(
+                self.ln,
+                b.name().to_string(),
+                arity,
+                args.len(),
+            ));
+            return None;
+        }
+        if b.on_str() && args[0].ty() == ValueType::Str {
+            let want = match b {
+                Builtin::Slice => ValueType::Int,
+                _ => ValueType::Str,
+            };
+            if let Some(arg) = args[1..].iter().find(|a| a.ty() != want) {
+                let msg = format!(
+                    "`{}` needs {} arguments after the string, but `{}` is of type {}",
+                    b.name(),
+                    want,
+                    arg,
+                    arg.ty()
+                );
+                return self.operand_err(msg);
+            }
+        }
+        let ty = match (b, args[0].ty()) {
+            (Builtin::Len, ValueType::List(_) | ValueType::Map(..) | ValueType::Str) => {
+                ValueType::Int
+            }
+            (
+                Builtin::Upper | Builtin::Lower | Builtin::Trim | Builtin::Replace | Builtin::Slice,
+                ValueType::Str,
+            ) => ValueType::Str,
+            (Builtin::Contains | Builtin::StartsWith | Builtin::EndsWith, ValueType::Str) => {
+                ValueType::Bool
+            }
+            (Builtin::Split, ValueType::Str) => ValueType::List(Box::new(ValueType::Str)),
+            (
+                Builtin::ToInt,
+                ValueType::Int | ValueType::Float | ValueType::Str | ValueType::Bool,
+            ) => ValueType::Int,
+            (Builtin::ToFloat, ValueType::Int | ValueType::Float | ValueType::Str) => {
+                ValueType::Float
+            }
+            (Builtin::ToStr, ty) if ty.printable() => ValueType::Str,
+            (Builtin::Has | Builtin::Remove, ValueType::Map(key, _)) if *key != args[1].ty() => {
+                let msg = format!("The key `{}` must be of type {}", args[1], key);
+                return self.operand_err(msg);
+            }
+            (Builtin::Has, ValueType::Map(..)) => ValueType::Bool,
+            (Builtin::Remove, ty @ ValueType::Map(..)) => ty,
+            (Builtin::Pop, ValueType::List(elem)) => *elem,
+            (Builtin::Push, ValueType::List(elem)) => {
+                if !elem.accepts(&args[1].ty()) {
+                    let msg = format!(
+                        "A list of {} cannot hold `{}` of type {}",
+                        elem,
+                        args[1],
+                        args[1].ty()
+                    );
+                    return self.operand_err(msg);
+                }
+                ValueType::List(elem)
+            }
+            (b, ty) => {
+                let wants = match b {
+                    Builtin::Len => "a string, a list or a map",
+                    Builtin::Push | Builtin::Pop => "a list",
+                    Builtin::Has | Builtin::Remove => "a map",
+                    Builtin::ToInt => "a number, a bool or a string",
+                    Builtin::ToFloat => "a number or a string",
+                    Builtin::ToStr => "a value that can be printed",
+                    _ => "a string",
+                };
+                let msg = format!(
+                    "`{}` needs {}, but `{}` is of type {}",
+                    b.name(),
+                    wants,
+                    args[0],
+                    ty
+                );
+                return self.operand_err(msg);
+            }
+        };
+        Some(Expr::Builtin(b, args, ty))
+    }
+
+    /// Type checks a binary operation, promoting int to float where needed.
+    fn binary(&mut self, l: Expr, op: BinOp, r: Expr) -> Option<Expr> {
+        use ValueType::*;
+        let ty = match (op, l.ty(), r.ty()) {
+            (BinOp::And | BinOp::Or, Bool, Bool) => Some(Bool),
+            (BinOp::And | BinOp::Or, _, _) => None,
+            (op, Int | Float, Int | Float) if op.is_comparison() => Some(Bool),
+            (op, Str, Str) if op.is_comparison() => Some(Bool),
+            (BinOp::Eq | BinOp::Ne, Bool, Bool) => Some(Bool),
+            (BinOp::Eq | BinOp::Ne, Enum(a), Enum(b)) if a == b => Some(Bool),
+            (op, _, _) if op.is_comparison() => None,
+            (_, Int, Int) => Some(Int),
+            // C only defines `%` on integers
+            (BinOp::Mod, _, _) => None,
+            (_, Int | Float, Int | Float) => Some(Float),
+            (BinOp::Add, Str, Str) => Some(Str),
+            _ => None,
+        };
+        match ty {
+            Some(ty) => Some(Expr::Bin(Box::new(l), op, Box::new(r), ty)),
+            None => {
+                let mut msg = format!(
+                    "Cannot apply `{}` to `{}` ({}) and `{}` ({})",
+                    op.symbol(),
+                    l,
+                    l.ty(),
+                    r,
+                    r.ty()
+                );
+                let number = |t: &ValueType| matches!(t, Int | Float);
+                if (l.ty() == Str && number(&r.ty())) || (number(&l.ty()) && r.ty() == Str) {
+                    msg.push_str(", strings and numbers cannot be mixed without a conversion");
+                }
+                self.errors.push(ErrT::InvalidOperand(self.ln, msg));
+                None
+            }
+        }
+    }
+}
+
+enum LexErr {
+    UnterminatedStr(String),
+    BadNumber(String),
+    BadChar(char),
+}
+
+impl LexErr {
+    fn into_err(self, ln: usize) -> ErrT {
+        match self {
+            LexErr::UnterminatedStr(s) => ErrT::UnMQ(ln, s),
+            LexErr::BadNumber(n) => ErrT::InValidVarVal(ln, n),
+            LexErr::BadChar('=') => {
+                ErrT::InvalidOperand(ln, "`=` assigns a value, use `==` to compare".to_string())
+            }
+            LexErr::BadChar(c) => ErrT::InvalidOperand(ln, format!("Unexpected character `{}`", c)),
+        }
+    }
+}
+
+/// Operator tokens, with two character operators first so they win over their prefixes.
+const OPS: &[&str] = &[
+    "==", "!=", "<=", ">=", "&&", "||", "+", "-", "*", "/", "%", "!", "<", ">", "(", ")", "{", "}",
+    "[", "]", ",", ":", ".", ";",
+];
+
+fn etokens(src: &str) -> Result<Vec<ETok>, LexErr> {
+    let mut toks = Vec::new();
+    let mut chars = src.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '"' || c == '\'' {
+            chars.next();
+            let mut s = String::new();
+            let mut closed = false;
+            while let Some(n) = chars.next() {
+                if n == c {
+                    closed = true;
+                    break;
+                }
+                if n == '\\' {
+                    match chars.next() {
+                        Some('n') => s.push('\n'),
+                        Some('t') => s.push('\t'),
+                        Some(e) => s.push(e),
+                        None => break,
+                    }
+                } else {
+                    s.push(n);
+                }
+            }
+            if !closed {
+                return Err(LexErr::UnterminatedStr(format!("{}{}", c, s)));
+            }
+            toks.push(ETok::Str(s));
+        } else if c.is_ascii_digit() {
+            let mut s = String::new();
+            while let Some(&n) = chars.peek() {
+                if n.is_ascii_digit() || n == '.' {
+                    s.push(n);
+                    chars.next();
+                } else {
+                    break;
+                }
+            }
+            if s.contains('.') {
+                toks.push(ETok::Float(s.parse().map_err(|_| LexErr::BadNumber(s))?));
+            } else {
+                toks.push(ETok::Int(s.parse().map_err(|_| LexErr::BadNumber(s))?));
+            }
+        } else if c.is_alphabetic() || c == '_' {
+            let mut s = String::new();
+            while let Some(&n) = chars.peek() {
+                if n.is_alphanumeric() || n == '_' {
+                    s.push(n);
+                    chars.next();
+                } else {
+                    break;
+                }
+            }
+            toks.push(ETok::Ident(s));
+        } else if let Some(op) = OPS
+            .iter()
+            .find(|op| chars.clone().take(op.len()).eq(op.chars()))
+        {
+            for _ in 0..op.len() {
+                chars.next();
+            }
+            toks.push(ETok::Op(op));
+        } else {
+            return Err(LexErr::BadChar(c));
+        }
+    }
+    Ok(toks)
+}
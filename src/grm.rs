@@ -40,27 +40,25 @@ pub fn pgrm(data: &mut String, grmf: &str) {
     let reader = io::BufReader::new(file);
     let mut grammar_map = HashMap::new();
     println!("Parsing grammar file...");
-    for line in reader.lines() {
-        if let Ok(line_str) = line {
-            let cleaned_line = line_str.trim();
-            if cleaned_line.is_empty() {
-                continue;
-            }
+    for line_str in reader.lines().map_while(Result::ok) {
+        let cleaned_line = line_str.trim();
+        if cleaned_line.is_empty() {
+            continue;
+        }
 
-            let mut parts = cleaned_line.splitn(2, '~');
-            if let (Some(original), Some(replacement)) = (parts.next(), parts.next()) {
-                let original = original.trim();
-                let replacement = replacement.trim();
-                if !original.is_empty() && !replacement.is_empty() {
-                    grammar_map.insert(replacement.to_string(), original.to_string());
-                } else {
-                    eprintln!("{}", ProgramError::InvalidGrammarFormat.description());
-                    return;
-                }
+        let mut parts = cleaned_line.splitn(2, '~');
+        if let (Some(original), Some(replacement)) = (parts.next(), parts.next()) {
+            let original = original.trim();
+            let replacement = replacement.trim();
+            if !original.is_empty() && !replacement.is_empty() {
+                grammar_map.insert(replacement.to_string(), original.to_string());
             } else {
                 eprintln!("{}", ProgramError::InvalidGrammarFormat.description());
                 return;
             }
+        } else {
+            eprintln!("{}", ProgramError::InvalidGrammarFormat.description());
+            return;
         }
     }
     let mut result = String::new();
@@ -20,6 +20,11 @@ pub fn help() {
         "build".yellow().bold(),
         "Lex, parse, generate C code, and compile it.".green()
     );
+    println!(
+        "  {}  {}",
+        "build <dir>".yellow().bold(),
+        "Build the project described by <dir>/neit.toml into <dir>/target.".green()
+    );
 
     println!("{}", "\nOPTIONS:".cyan().bold());
     println!(
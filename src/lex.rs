@@ -59,6 +59,9 @@ pub fn lex(code: &str, toks: &mut Vec<Tokens>) {
             if c == '#' && iter.peek() == Some(&'#') {
                 iter.next(); // Skip second '#'
                 in_multiline_comment = false;
+            } else if c == '\n' {
+                // Keep line numbers in sync for code after the comment
+                toks.push(Tokens::mktok(TokType::EOL, String::from("\n")));
             }
             continue;
         } else if nl(c, next, &mut iter) {
@@ -84,13 +87,12 @@ pub fn lex(code: &str, toks: &mut Vec<Tokens>) {
             '#' => {
                 while let Some(&next_c) = iter.peek() {
                     if next_c == '\n' {
-                        toks.push(Tokens::mktok(TokType::EOL, String::from("\n")));
-                        break;
+                        break; // The newline itself is handled by the main loop
                     }
                     iter.next(); // Consume characters in single-line comment
                 }
             }
-            ' ' => {
+            ' ' | '\t' => {
                 if !curt.is_empty() {
                     if fw {
                         toks.push(Tokens::mktok(TokType::CMD, curt.clone()));
@@ -100,7 +102,7 @@ pub fn lex(code: &str, toks: &mut Vec<Tokens>) {
                     }
                     curt.clear();
                 }
-                toks.push(Tokens::mktok(TokType::SPACE, String::from(" ")));
+                toks.push(Tokens::mktok(TokType::SPACE, c.to_string()));
             }
             _ if c.is_ascii_punctuation() => {
                 if !curt.is_empty() {
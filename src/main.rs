@@ -1,3 +1,5 @@
+#![allow(clippy::upper_case_acronyms)]
+
 use std::{env::args, process::exit};
 
 use build::build;
@@ -8,22 +10,27 @@ use help::help;
 pub mod build;
 pub mod codegen;
 mod err;
+mod expr;
 pub mod grm;
 pub mod help;
 mod lex;
+mod manifest;
+mod module;
 pub mod nrunp;
 pub mod nulibc;
 mod p;
 mod p2;
 pub mod p3;
 mod run;
+mod scope;
+mod typeck;
 
 fn main() {
     match enable_ansi_support() {
         Ok(_) => {}
         Err(e) => {
             eprintln!("{}","Unable to enable ansii colors support :~ ANSI Code will be visible along lines , please ignore!".bright_yellow());
-            eprintln!("{}{}", "Specific Error Message :~ ", e);
+            eprintln!("Specific Error Message :~ {}", e);
         }
     }
 
@@ -36,7 +43,7 @@ fn main() {
     match cmd {
         "h" | "help" => help(),
         "build" => build(&args),
-        "run" => { /*build the code*/ }
+        "run" => run::run_file(&args),
         _ => {
             eprintln!("Error :~ Unknown Command : {}\n", cmd);
             help();
@@ -0,0 +1,291 @@
+use std::collections::HashSet;
+
+/// The settings of a project, read from the `neit.toml` at its root.
+///
+/// Only the part of TOML a manifest needs is understood: an optional `[project]` header,
+/// `key = value` lines with strings, integers, booleans or one-line arrays of strings, and
+/// `#` comments.
+#[derive(Debug, PartialEq)]
+pub struct Manifest {
+    pub name: String,
+    /// The file the program starts in, relative to the project directory.
+    pub entry: String,
+    /// The name of the executable placed in `target/`.
+    pub output: String,
+    pub opt: i32,
+    pub static_link: bool,
+    pub grammar: Option<String>,
+    /// C files compiled and linked in alongside the generated code.
+    pub c_sources: Vec<String>,
+}
+
+enum Value {
+    Str(String),
+    Int(i32),
+    Bool(bool),
+    List(Vec<String>),
+}
+
+impl Manifest {
+    pub fn parse(text: &str) -> Result<Manifest, String> {
+        let mut name = None;
+        let mut manifest = Manifest {
+            name: String::new(),
+            entry: "main.nsc".to_string(),
+            output: String::new(),
+            opt: -1,
+            static_link: false,
+            grammar: None,
+            c_sources: Vec::new(),
+        };
+        let mut seen = HashSet::new();
+
+        for (i, line) in text.lines().enumerate() {
+            let at = |msg: String| format!("line {}: {}", i + 1, msg);
+            let line = strip_comment(line).trim();
+            if line.is_empty() || line == "[project]" {
+                continue;
+            }
+            if line.starts_with('[') {
+                return Err(at(format!(
+                    "Unknown section `{}`, only `[project]` is read",
+                    line
+                )));
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(at(format!("Expected `key = value`, found `{}`", line)));
+            };
+            let key = key.trim();
+            if !seen.insert(key.to_string()) {
+                return Err(at(format!("`{}` is set twice", key)));
+            }
+            let value = parse_value(value.trim()).map_err(&at)?;
+
+            let wrong = |want: &str| at(format!("`{}` must be {}", key, want));
+            match (key, value) {
+                ("name", Value::Str(s)) => name = Some(s),
+                ("entry", Value::Str(s)) => manifest.entry = s,
+                ("output", Value::Str(s)) => manifest.output = s,
+                ("opt", Value::Int(n)) if (0..=4).contains(&n) => manifest.opt = n,
+                ("static", Value::Bool(b)) => manifest.static_link = b,
+                ("grammar", Value::Str(s)) => manifest.grammar = Some(s),
+                ("c_sources", Value::List(l)) => manifest.c_sources = l,
+                ("name" | "entry" | "output" | "grammar", _) => return Err(wrong("a string")),
+                ("opt", _) => return Err(wrong("a number from 0 to 4")),
+                ("static", _) => return Err(wrong("`true` or `false`")),
+                ("c_sources", _) => return Err(wrong("a list of strings")),
+                _ => return Err(at(format!("Unknown key `{}`", key))),
+            }
+        }
+
+        manifest.name = name.ok_or("The project has no `name`")?;
+        if manifest.output.is_empty() {
+            manifest.output = manifest.name.clone();
+        }
+        Ok(manifest)
+    }
+}
+
+/// Cuts a `#` comment off a line, leaving any `#` inside a string alone.
+fn strip_comment(line: &str) -> &str {
+    let mut in_str = false;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_str => escaped = true,
+            '"' => in_str = !in_str,
+            '#' if !in_str => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+fn parse_value(text: &str) -> Result<Value, String> {
+    if let Some(inner) = text.strip_prefix('[') {
+        let inner = inner
+            .strip_suffix(']')
+            .ok_or("A list must open and close on the same line")?;
+        let mut items = Vec::new();
+        let mut rest = inner.trim();
+        while !rest.is_empty() {
+            let (item, after) = parse_str(rest)?;
+            items.push(item);
+            rest = after.trim_start();
+            rest = match rest.strip_prefix(',') {
+                Some(after) => after.trim_start(),
+                None if rest.is_empty() => rest,
+                None => return Err(format!("Expected `,` in the list before `{}`", rest)),
+            };
+        }
+        return Ok(Value::List(items));
+    }
+    if text.starts_with('"') {
+        let (s, rest) = parse_str(text)?;
+        if !rest.trim().is_empty() {
+            return Err(format!("Unexpected `{}` after the string", rest.trim()));
+        }
+        return Ok(Value::Str(s));
+    }
+    match text {
+        "true" => Ok(Value::Bool(true)),
+        "false" => Ok(Value::Bool(false)),
+        _ => text
+            .parse()
+            .map(Value::Int)
+            .map_err(|_| format!("`{}` is not a string, number, boolean or list", text)),
+    }
+}
+
+/// Reads a `"..."` string off the front of `text`, returning it and what follows.
+fn parse_str(text: &str) -> Result<(String, &str), String> {
+    let body = text
+        .strip_prefix('"')
+        .ok_or_else(|| format!("Expected a string, found `{}`", text))?;
+    let mut out = String::new();
+    let mut chars = body.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Ok((out, &body[i + 1..])),
+            '\\' => match chars.next() {
+                Some((_, 'n')) => out.push('\n'),
+                Some((_, 't')) => out.push('\t'),
+                Some((_, c @ ('"' | '\\'))) => out.push(c),
+                _ => return Err("Unknown escape in a string".to_string()),
+            },
+            c => out.push(c),
+        }
+    }
+    Err("A string is missing its closing `\"`".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn err(text: &str) -> String {
+        Manifest::parse(text).expect_err("the manifest should be rejected")
+    }
+
+    #[test]
+    fn reads_every_key() {
+        let m = Manifest::parse(
+            r#"
+# a project
+[project]
+name = "demo"          # required
+entry = "src/app.nsc"
+output = "demo-bin"
+opt = 2
+static = true
+grammar = "grammar.txt"
+c_sources = ["a.c", "b.c"]
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            m,
+            Manifest {
+                name: "demo".to_string(),
+                entry: "src/app.nsc".to_string(),
+                output: "demo-bin".to_string(),
+                opt: 2,
+                static_link: true,
+                grammar: Some("grammar.txt".to_string()),
+                c_sources: vec!["a.c".to_string(), "b.c".to_string()],
+            }
+        );
+    }
+
+    #[test]
+    fn fills_in_defaults() {
+        let m = Manifest::parse("name = \"demo\"").unwrap();
+        assert_eq!(m.entry, "main.nsc");
+        assert_eq!(m.output, "demo");
+        assert_eq!(m.opt, -1);
+        assert!(!m.static_link);
+        assert_eq!(m.grammar, None);
+        assert!(m.c_sources.is_empty());
+    }
+
+    #[test]
+    fn keeps_hashes_and_escapes_inside_strings() {
+        let m = Manifest::parse(r#"name = "a#b \"c\" \\ \t" # comment"#).unwrap();
+        assert_eq!(m.name, "a#b \"c\" \\ \t");
+        assert_eq!(strip_comment(r##"x = "\"#" # y"##), r##"x = "\"#" "##);
+    }
+
+    #[test]
+    fn reads_lists() {
+        let m = Manifest::parse("name = \"d\"\nc_sources = []").unwrap();
+        assert!(m.c_sources.is_empty());
+        let m = Manifest::parse("name = \"d\"\nc_sources = [ \"a.c\" , \"b,c.c\", ]").unwrap();
+        assert_eq!(m.c_sources, ["a.c", "b,c.c"]);
+    }
+
+    #[test]
+    fn rejects_bad_structure() {
+        assert_eq!(err(""), "The project has no `name`");
+        assert_eq!(
+            err("name = \"a\"\nname = \"b\""),
+            "line 2: `name` is set twice"
+        );
+        assert_eq!(
+            err("[deps]\nname = \"a\""),
+            "line 1: Unknown section `[deps]`, only `[project]` is read"
+        );
+        assert_eq!(err("name"), "line 1: Expected `key = value`, found `name`");
+        assert_eq!(err("name = \"a\"\nfoo = 1"), "line 2: Unknown key `foo`");
+    }
+
+    #[test]
+    fn rejects_wrong_types() {
+        assert_eq!(err("name = 1"), "line 1: `name` must be a string");
+        assert_eq!(
+            err("name = \"a\"\nopt = 5"),
+            "line 2: `opt` must be a number from 0 to 4"
+        );
+        assert_eq!(
+            err("name = \"a\"\nopt = -1"),
+            "line 2: `opt` must be a number from 0 to 4"
+        );
+        assert_eq!(
+            err("name = \"a\"\nstatic = 1"),
+            "line 2: `static` must be `true` or `false`"
+        );
+        assert_eq!(
+            err("name = \"a\"\nc_sources = \"a.c\""),
+            "line 2: `c_sources` must be a list of strings"
+        );
+        assert_eq!(
+            err("name = yes"),
+            "line 1: `yes` is not a string, number, boolean or list"
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_values() {
+        assert_eq!(
+            err("name = \"a"),
+            "line 1: A string is missing its closing `\"`"
+        );
+        assert_eq!(err(r#"name = "a\q""#), "line 1: Unknown escape in a string");
+        assert_eq!(
+            err("name = \"a\" \"b\""),
+            "line 1: Unexpected `\"b\"` after the string"
+        );
+        assert_eq!(
+            err("c_sources = [\"a.c\""),
+            "line 1: A list must open and close on the same line"
+        );
+        assert_eq!(
+            err("c_sources = [\"a.c\" \"b.c\"]"),
+            "line 1: Expected `,` in the list before `\"b.c\"`"
+        );
+        assert_eq!(
+            err("c_sources = [1]"),
+            "line 1: Expected a string, found `1`"
+        );
+    }
+}
@@ -0,0 +1,234 @@
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use crate::{
+    err::ErrT,
+    grm,
+    lex::{lex, Tokens},
+    p::{parse_body, report, Binding, CmdSig, Ctx, Param, NST},
+    scope::Symbols,
+    typeck,
+};
+
+/// Statements allowed at the top of a module, which may only declare things.
+pub const MODULE_ITEMS: &[&str] = &["cmd", "shape", "enum", "use"];
+
+/// What a module makes available to the files that `use` it.
+#[derive(Debug, Clone)]
+pub struct Module {
+    /// The name its commands are called through, taken from the file name.
+    pub ns: String,
+    /// Its own commands; the ones it imported itself stay private.
+    pub cmds: Vec<(String, CmdSig)>,
+    /// Every shape and enum it can see, the imported ones too, as their values can be given by
+    /// its commands or held in its shapes.
+    pub shapes: HashMap<String, Vec<Param>>,
+    pub enums: HashMap<String, Vec<String>>,
+}
+
+/// Modules seen while parsing one program, shared by every file in it.
+#[derive(Debug, Default)]
+pub struct Modules {
+    /// The files being parsed, from the main file down to the innermost `use`.
+    pub stack: Vec<PathBuf>,
+    /// Every module parsed so far, so each one is only parsed and emitted once.
+    pub done: HashMap<PathBuf, Module>,
+    /// Modules with errors, which have been reported already.
+    pub failed: HashSet<PathBuf>,
+    /// The grammar file the program is written in, which every module is read with as well.
+    pub grammar: Option<String>,
+}
+
+/// Handles `use path.nsc` in `file`: parses the module the first time it is seen, then
+/// makes its commands callable as `name.cmd` along with its shapes and enums.
+pub fn parse_use(
+    text: &str,
+    ln: usize,
+    file: &str,
+    nst: &mut Vec<NST>,
+    errors: &mut Vec<ErrT>,
+    vars: &Symbols<Binding>,
+    ctx: &mut Ctx,
+) {
+    let bad = |errors: &mut Vec<ErrT>, why: String| errors.push(ErrT::BadUse(ln, why));
+    // The commands of a module are emitted alongside the file's own, which only happens for
+    // those declared at the top level
+    if !vars.at_top() {
+        return bad(errors, "`use` only works at the top of a file.".to_string());
+    }
+    if text.is_empty() {
+        return bad(errors, "`use` needs the path of a `.nsc` file.".to_string());
+    }
+
+    // Paths are relative to the file doing the import
+    let path = Path::new(file).parent().unwrap_or(Path::new("")).join(text);
+    let shown = path.display().to_string();
+    let Ok(key) = path.canonicalize() else {
+        return bad(errors, format!("Cannot find the module `{}`.", shown));
+    };
+    let ns = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    if ns.is_empty() || !ns.chars().all(|c| c.is_alphanumeric() || c == '_') {
+        return bad(
+            errors,
+            format!(
+                "`{}` cannot name a module, use letters, digits and `_`.",
+                ns
+            ),
+        );
+    }
+
+    let modules = Rc::clone(&ctx.modules);
+    if let Some(at) = modules.borrow().stack.iter().position(|p| *p == key) {
+        let chain: Vec<String> = modules.borrow().stack[at..]
+            .iter()
+            .chain([&key])
+            .map(|p| format!("`{}`", name_of(p)))
+            .collect();
+        return bad(
+            errors,
+            format!(
+                "Importing `{}` forms a cycle: {}.",
+                text,
+                chain.join(" -> ")
+            ),
+        );
+    }
+    // Commands are linked as `ns.cmd`, so two modules cannot share a name
+    if let Some((other, _)) = modules
+        .borrow()
+        .done
+        .iter()
+        .find(|(p, m)| m.ns == ns && **p != key)
+    {
+        return bad(
+            errors,
+            format!(
+                "`{}` and `{}` are both named `{}`, rename one of them.",
+                shown,
+                other.display(),
+                ns
+            ),
+        );
+    }
+
+    let broken = format!("The module `{}` has errors, see above.", shown);
+    if modules.borrow().failed.contains(&key) {
+        return bad(errors, broken);
+    }
+    let cached = modules.borrow().done.get(&key).cloned();
+    let module = match cached {
+        Some(module) => module,
+        None => {
+            let Some((module, items)) = load(&path, &key, &ns, ctx) else {
+                modules.borrow_mut().failed.insert(key);
+                return bad(errors, broken);
+            };
+            nst.extend(items);
+            modules.borrow_mut().done.insert(key, module.clone());
+            module
+        }
+    };
+
+    for (name, fields) in module.shapes {
+        if ctx.shapes.get(&name).is_some_and(|f| *f != fields) || ctx.enums.contains_key(&name) {
+            return bad(
+                errors,
+                format!(
+                    "`{}` declares a type `{}` that already exists here.",
+                    shown, name
+                ),
+            );
+        }
+        ctx.shapes.insert(name, fields);
+    }
+    for (name, variants) in module.enums {
+        if ctx.enums.get(&name).is_some_and(|v| *v != variants) || ctx.shapes.contains_key(&name) {
+            return bad(
+                errors,
+                format!(
+                    "`{}` declares a type `{}` that already exists here.",
+                    shown, name
+                ),
+            );
+        }
+        ctx.enums.insert(name, variants);
+    }
+    for (name, sig) in module.cmds {
+        ctx.cmds.insert(format!("{}.{}", module.ns, name), sig);
+    }
+}
+
+/// Reads, lexes, parses and type checks a module, printing its errors under its own file name.
+fn load(path: &Path, key: &Path, ns: &str, ctx: &Ctx) -> Option<(Module, Vec<NST>)> {
+    let mut code = fs::read_to_string(path).ok()?;
+    let grammar = ctx.modules.borrow().grammar.clone();
+    if let Some(grmf) = grammar {
+        grm::pgrm(&mut code, &grmf);
+    }
+    let mut toks = Tokens::new();
+    lex(&code, &mut toks);
+    let codes: Vec<&str> = code.split('\n').collect();
+    let file = path.display().to_string();
+
+    let mut inner = Ctx {
+        module: Some(ns.to_string()),
+        modules: Rc::clone(&ctx.modules),
+        ..Ctx::default()
+    };
+    let mut errors = Vec::new();
+    ctx.modules.borrow_mut().stack.push(key.to_path_buf());
+    let nst = parse_body(
+        &toks,
+        &codes,
+        &file,
+        &mut errors,
+        &Symbols::new(),
+        &mut inner,
+        1,
+    );
+    ctx.modules.borrow_mut().stack.pop();
+    typeck::check(&nst, &inner, &mut errors);
+
+    if !errors.is_empty() {
+        report(&errors, &codes, &file);
+        return None;
+    }
+    let cmds = inner
+        .cmds
+        .into_iter()
+        .filter(|(name, _)| !name.contains('.'))
+        .collect();
+    let module = Module {
+        ns: ns.to_string(),
+        cmds,
+        shapes: inner.shapes,
+        enums: inner.enums,
+    };
+    // The lines point into the module, which has been checked, so they are of no more use
+    Some((module, nst.into_iter().map(|(_, stmt)| stmt).collect()))
+}
+
+fn name_of(path: &Path) -> String {
+    path.file_name()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.display().to_string())
+}
+
+/// Starts the shared module state for a program whose main file is `file`, written in
+/// `grammar` when one is given.
+pub fn modules_for(file: &str, grammar: Option<&str>) -> Rc<RefCell<Modules>> {
+    let stack = Path::new(file).canonicalize().into_iter().collect();
+    Rc::new(RefCell::new(Modules {
+        stack,
+        grammar: grammar.map(str::to_string),
+        ..Modules::default()
+    }))
+}
@@ -13,9 +13,12 @@ use std::{thread::sleep, time::Duration};
 
 use colored::Colorize;
 
-use crate::p::{VVal, NST};
+use crate::{
+    p::{PrintPart, Stmt, NST},
+    p2::ValueType,
+};
 
-pub fn nrunp(nst: &Vec<NST>, target: &str) -> String {
+pub fn nrunp(nst: &[Stmt], target: &str) -> String {
     if target == "linux" || target == "windows" {
         let bc = genc(nst, target);
         println!("{}", bc);
@@ -26,36 +29,43 @@ pub fn nrunp(nst: &Vec<NST>, target: &str) -> String {
     }
 }
 
-pub fn genc(nst: &Vec<NST>, target: &str) -> String {
+#[allow(clippy::only_used_in_recursion)]
+pub fn genc(nst: &[Stmt], target: &str) -> String {
     let mut bc = String::with_capacity(1024);
 
-    for nst in nst {
+    for (_, nst) in nst {
         match nst {
             NST::NCLRSCRN => {
                 bc.push_str("cls;"); // Clear screen command
             }
-            NST::PRINT(txt) => {
+            NST::PRINT(parts) => {
+                let txt: String = parts
+                    .iter()
+                    .map(|p| match p {
+                        PrintPart::Text(t) => t.clone(),
+                        PrintPart::Expr(e) => format!("{{{}}}", e),
+                    })
+                    .collect();
                 bc.push_str(&format!(">{};", txt)); // Print command with separator
             }
-            NST::Func(name, args, nsts) => {
+            NST::Func(name, args, _, nsts) => {
                 let body = genc(nsts, target);
                 let body_cleaned = body.replace("\n", ";");
+                let args: Vec<&str> = args.iter().map(|p| p.name.as_str()).collect();
                 bc.push_str(&format!("@{}({})[{}];", name, args.join(","), body_cleaned));
             }
             NST::Var(v) => {
-                let var_type = match v.value {
-                    VVal::Str(_) => "s",
-                    VVal::Int(_) => "i",
-                    VVal::F(_) => "f",
-                    VVal::VarRef(_, _) => "v",
-                };
-
-                let var_value = match &v.value {
-                    VVal::Str(t) => t,
-                    VVal::Int(t) => &t.to_string(),
-                    VVal::F(t) => &t.to_string(),
-                    VVal::VarRef(_, t) => t,
+                let var_type = match v.ty {
+                    ValueType::Str => "s",
+                    ValueType::Int => "i",
+                    ValueType::Bool => "b",
+                    ValueType::Float => "f",
+                    ValueType::List(_) => "l",
+                    ValueType::Map(..) => "m",
+                    ValueType::Shape(_) => "r",
+                    ValueType::Enum(_) => "e",
                 };
+                let var_value = v.value.to_string();
 
                 bc.push_str(&format!(".{}{}{};", v.name, var_type, var_value));
             }
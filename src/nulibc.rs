@@ -1,6 +1,11 @@
-pub static NULIBC: &'static str = r#"
+pub static NULIBC: &str = r#"
+/* nanosleep is POSIX, which a strict C standard hides */
+#define _POSIX_C_SOURCE 199309L
+#include "nulibc.h"
+#include <errno.h>
 #include <stdio.h>
 #include <stdlib.h>
+#include <time.h>
 int strcmp(const char *str1, const char *str2) {
     while (*str1 != '\0' && *str2 != '\0') {
         if (*str1 != *str2) {
@@ -11,6 +16,616 @@ int strcmp(const char *str1, const char *str2) {
     }
     return (unsigned char)(*str1) - (unsigned char)(*str2);
 }
+char *__NSTRDUP__(const char *s) {
+    size_t len = 0;
+    while (s[len] != '\0') {
+        len++;
+    }
+    char *copy = malloc(len + 1);
+    if (copy == NULL) {
+        perror("Out of memory");
+        exit(1);
+    }
+    for (size_t i = 0; i <= len; i++) {
+        copy[i] = s[i];
+    }
+    return copy;
+}
+char *__NSTRCAT__(const char *a, const char *b) {
+    size_t la = 0, lb = 0;
+    while (a[la] != '\0') {
+        la++;
+    }
+    while (b[lb] != '\0') {
+        lb++;
+    }
+    char *out = malloc(la + lb + 1);
+    if (out == NULL) {
+        perror("Out of memory");
+        exit(1);
+    }
+    for (size_t i = 0; i < la; i++) {
+        out[i] = a[i];
+    }
+    for (size_t i = 0; i <= lb; i++) {
+        out[la + i] = b[i];
+    }
+    return out;
+}
+char *__NSTR_SET__(char *old, char *s) {
+    free(old);
+    return s;
+}
+void __NFREE__(char **s) {
+    free(*s);
+}
+static char *__NSTR_SUB__(const char *s, int from, int n) {
+    char *out = malloc(n + 1);
+    if (out == NULL) {
+        perror("Out of memory");
+        exit(1);
+    }
+    for (int i = 0; i < n; i++) {
+        out[i] = s[from + i];
+    }
+    out[n] = '\0';
+    return out;
+}
+static bool __NSTR_IS_AT__(const char *s, int at, const char *part) {
+    for (int i = 0; part[i] != '\0'; i++) {
+        if (s[at + i] != part[i]) {
+            return false;
+        }
+    }
+    return true;
+}
+static bool __NSTR_SPACE__(char c) {
+    return c == ' ' || c == '\t' || c == '\n' || c == '\r' || c == '\v' || c == '\f';
+}
+/* Whether byte `i` starts a character, which every byte but a UTF-8 continuation byte does */
+static bool __NSTR_BOUNDARY__(const char *s, int i) {
+    return ((unsigned char)s[i] & 0xC0) != 0x80;
+}
+/* The number of bytes in the character that starts at byte `i` */
+static int __NSTR_CHAR_LEN__(const char *s, int i) {
+    int n = 1;
+    while (s[i + n] != '\0' && !__NSTR_BOUNDARY__(s, i + n)) {
+        n++;
+    }
+    return n;
+}
+int __NSTR_LEN__(const char *s) {
+    int n = 0;
+    while (s[n] != '\0') {
+        n++;
+    }
+    return n;
+}
+char *__NSTR_AT__(const char *s, int i) {
+    int len = __NSTR_LEN__(s);
+    if (i < 0 || i >= len) {
+        fprintf(stderr, "Runtime error :~ Index %d is out of range for a string of length %d\n", i, len);
+        exit(1);
+    }
+    if (!__NSTR_BOUNDARY__(s, i)) {
+        fprintf(stderr, "Runtime error :~ Index %d is in the middle of a character\n", i);
+        exit(1);
+    }
+    return __NSTR_SUB__(s, i, __NSTR_CHAR_LEN__(s, i));
+}
+char *__NSTR_UPPER__(const char *s) {
+    char *out = __NSTRDUP__(s);
+    for (int i = 0; out[i] != '\0'; i++) {
+        if (out[i] >= 'a' && out[i] <= 'z') {
+            out[i] -= 'a' - 'A';
+        }
+    }
+    return out;
+}
+char *__NSTR_LOWER__(const char *s) {
+    char *out = __NSTRDUP__(s);
+    for (int i = 0; out[i] != '\0'; i++) {
+        if (out[i] >= 'A' && out[i] <= 'Z') {
+            out[i] += 'a' - 'A';
+        }
+    }
+    return out;
+}
+char *__NSTR_TRIM__(const char *s) {
+    int from = 0, to = __NSTR_LEN__(s);
+    while (from < to && __NSTR_SPACE__(s[from])) {
+        from++;
+    }
+    while (to > from && __NSTR_SPACE__(s[to - 1])) {
+        to--;
+    }
+    return __NSTR_SUB__(s, from, to - from);
+}
+bool __NSTR_HAS__(const char *s, const char *part) {
+    int len = __NSTR_LEN__(s), n = __NSTR_LEN__(part);
+    for (int i = 0; i + n <= len; i++) {
+        if (__NSTR_IS_AT__(s, i, part)) {
+            return true;
+        }
+    }
+    return false;
+}
+bool __NSTR_STARTS__(const char *s, const char *part) {
+    return __NSTR_LEN__(part) <= __NSTR_LEN__(s) && __NSTR_IS_AT__(s, 0, part);
+}
+bool __NSTR_ENDS__(const char *s, const char *part) {
+    int len = __NSTR_LEN__(s), n = __NSTR_LEN__(part);
+    return n <= len && __NSTR_IS_AT__(s, len - n, part);
+}
+char *__NSTR_REPLACE__(const char *s, const char *from, const char *to) {
+    int len = __NSTR_LEN__(s), nf = __NSTR_LEN__(from), nt = __NSTR_LEN__(to), count = 0;
+    if (nf == 0) {
+        return __NSTRDUP__(s);
+    }
+    for (int i = 0; i + nf <= len;) {
+        if (__NSTR_IS_AT__(s, i, from)) {
+            count++;
+            i += nf;
+        } else {
+            i++;
+        }
+    }
+    char *out = malloc(len + count * (nt - nf) + 1);
+    if (out == NULL) {
+        perror("Out of memory");
+        exit(1);
+    }
+    int o = 0;
+    for (int i = 0; i < len;) {
+        if (i + nf <= len && __NSTR_IS_AT__(s, i, from)) {
+            for (int j = 0; j < nt; j++) {
+                out[o++] = to[j];
+            }
+            i += nf;
+        } else {
+            out[o++] = s[i++];
+        }
+    }
+    out[o] = '\0';
+    return out;
+}
+__NLIST__ *__NSTR_SPLIT__(const char *s, const char *sep) {
+    __NLIST__ *l = __NLIST_NEW__();
+    int len = __NSTR_LEN__(s), n = __NSTR_LEN__(sep), start = 0;
+    if (n == 0) {
+        for (int i = 0; i < len;) {
+            int n = __NSTR_CHAR_LEN__(s, i);
+            __NLIST_PUSH__(l, (__NVAL__){.s = __NSTR_SUB__(s, i, n)});
+            i += n;
+        }
+        return l;
+    }
+    for (int i = 0; i + n <= len;) {
+        if (__NSTR_IS_AT__(s, i, sep)) {
+            __NLIST_PUSH__(l, (__NVAL__){.s = __NSTR_SUB__(s, start, i - start)});
+            i += n;
+            start = i;
+        } else {
+            i++;
+        }
+    }
+    __NLIST_PUSH__(l, (__NVAL__){.s = __NSTR_SUB__(s, start, len - start)});
+    return l;
+}
+char *__NSTR_SLICE__(const char *s, int from, int to) {
+    int len = __NSTR_LEN__(s);
+    if (from < 0 || to < from || to > len) {
+        fprintf(stderr, "Runtime error :~ The slice %d..%d is out of range for a string of length %d\n", from, to, len);
+        exit(1);
+    }
+    if (!__NSTR_BOUNDARY__(s, from) || !__NSTR_BOUNDARY__(s, to)) {
+        fprintf(stderr, "Runtime error :~ The slice %d..%d cuts a character in two\n", from, to);
+        exit(1);
+    }
+    return __NSTR_SUB__(s, from, to - from);
+}
+int __NTO_INT__(const char *s) {
+    char *t = __NSTR_TRIM__(s), *end;
+    long long v = strtoll(t, &end, 10);
+    if (*t == '\0' || *end != '\0' || v < -2147483648LL || v > 2147483647LL) {
+        fprintf(stderr, "Runtime error :~ Cannot convert \"%s\" to an int\n", s);
+        exit(1);
+    }
+    free(t);
+    return (int)v;
+}
+float __NTO_FLOAT__(const char *s) {
+    char *t = __NSTR_TRIM__(s), *end;
+    float v = strtof(t, &end);
+    /* strtof also reads hex floats, which the interpreter does not */
+    if (*t == '\0' || *end != '\0' || __NSTR_HAS__(t, "x") || __NSTR_HAS__(t, "X")) {
+        fprintf(stderr, "Runtime error :~ Cannot convert \"%s\" to a float\n", s);
+        exit(1);
+    }
+    free(t);
+    return v;
+}
+/* Checks a `for` step that is only known at run time */
+int __NSTEP__(int step) {
+    if (step == 0) {
+        fprintf(stderr, "Runtime error :~ The step of a `for` loop cannot be 0\n");
+        exit(1);
+    }
+    return step;
+}
+/* Truncates a float to an int, which C leaves undefined for NaN and out of range values */
+int __NFLOAT_INT__(float f) {
+    if (!(f >= -2147483648.0f && f < 2147483648.0f)) {
+        fprintf(stderr, "Runtime error :~ Cannot convert %f to an int\n", f);
+        exit(1);
+    }
+    return (int)f;
+}
+/* Sleeps for `ms` milliseconds, which usleep can't do past a 32 bit count of microseconds */
+void __NWAIT__(long long ms) {
+    struct timespec left = {.tv_sec = ms / 1000, .tv_nsec = ms % 1000 * 1000000};
+    while (nanosleep(&left, &left) == -1 && errno == EINTR) {
+    }
+}
+/* Int division and remainder, which C leaves undefined for 0 and for INT_MIN by -1 */
+int __NDIV__(int a, int b) {
+    if (b == 0) {
+        fprintf(stderr, "Runtime error :~ Division by zero\n");
+        exit(1);
+    }
+    if (b == -1) {
+        return (int)(0u - (unsigned)a);
+    }
+    return a / b;
+}
+int __NMOD__(int a, int b) {
+    if (b == 0) {
+        fprintf(stderr, "Runtime error :~ Modulo by zero\n");
+        exit(1);
+    }
+    if (b == -1) {
+        return 0;
+    }
+    return a % b;
+}
+char *__NTAKEIN__() {
+    size_t len = 0, cap = 64;
+    char *line = malloc(cap);
+    int c;
+    if (line == NULL) {
+        perror("Out of memory");
+        exit(1);
+    }
+    fflush(stdout);
+    while ((c = getchar()) != EOF && c != '\n') {
+        if (len + 1 >= cap) {
+            cap *= 2;
+            line = realloc(line, cap);
+            if (line == NULL) {
+                perror("Out of memory");
+                exit(1);
+            }
+        }
+        line[len++] = (char)c;
+    }
+    if (len > 0 && line[len - 1] == '\r') {
+        len--;
+    }
+    line[len] = '\0';
+    return line;
+}
+void *__NBOX__(const void *src, size_t n) {
+    unsigned char *copy = malloc(n);
+    if (copy == NULL) {
+        perror("Out of memory");
+        exit(1);
+    }
+    for (size_t i = 0; i < n; i++) {
+        copy[i] = ((const unsigned char *)src)[i];
+    }
+    return copy;
+}
+__NLIST__ *__NLIST_NEW__(void) {
+    __NLIST__ *l = malloc(sizeof(__NLIST__));
+    if (l == NULL) {
+        perror("Out of memory");
+        exit(1);
+    }
+    l->len = 0;
+    l->cap = 0;
+    l->items = NULL;
+    return l;
+}
+__NLIST__ *__NLIST_OF__(int n, ...) {
+    __NLIST__ *l = __NLIST_NEW__();
+    va_list ap;
+    va_start(ap, n);
+    for (int i = 0; i < n; i++) {
+        __NLIST_PUSH__(l, va_arg(ap, __NVAL__));
+    }
+    va_end(ap);
+    return l;
+}
+/* Each element of a list of strings gets a copy of its own, and the string is freed if unused */
+__NLIST__ *__NLIST_FILL__(int n, __NVAL__ v, bool str) {
+    __NLIST__ *l = __NLIST_NEW__();
+    for (int i = 0; i < n; i++) {
+        __NLIST_PUSH__(l, str && i > 0 ? (__NVAL__){.s = __NSTRDUP__(v.s)} : v);
+    }
+    if (str && n <= 0) {
+        free(v.s);
+    }
+    return l;
+}
+__NLIST__ *__NLIST_PUSH__(__NLIST__ *l, __NVAL__ v) {
+    if (l->len == l->cap) {
+        l->cap = l->cap == 0 ? 8 : l->cap * 2;
+        l->items = realloc(l->items, sizeof(__NVAL__) * l->cap);
+        if (l->items == NULL) {
+            perror("Out of memory");
+            exit(1);
+        }
+    }
+    l->items[l->len++] = v;
+    return l;
+}
+__NVAL__ __NLIST_POP__(__NLIST__ *l) {
+    if (l->len == 0) {
+        fprintf(stderr, "Runtime error :~ Cannot pop from an empty list\n");
+        exit(1);
+    }
+    return l->items[--l->len];
+}
+__NVAL__ *__NLIST_AT__(__NLIST__ *l, int i) {
+    if (i < 0 || i >= l->len) {
+        fprintf(stderr, "Runtime error :~ Index %d is out of range for a list of length %d\n", i, l->len);
+        exit(1);
+    }
+    return &l->items[i];
+}
+int __NLIST_LEN__(__NLIST__ *l) {
+    return l->len;
+}
+void __NLIST_FREE__(__NLIST__ **l) {
+    free((*l)->items);
+    free(*l);
+}
+void __NLIST_FREE_STRS__(__NLIST__ **l) {
+    for (int i = 0; i < (*l)->len; i++) {
+        free((*l)->items[i].s);
+    }
+    __NLIST_FREE__(l);
+}
+static void __NBUF_ADD__(char **buf, size_t *len, size_t *cap, const char *s) {
+    size_t n = 0;
+    while (s[n] != '\0') {
+        n++;
+    }
+    while (*len + n + 1 > *cap) {
+        *cap = *cap == 0 ? 64 : *cap * 2;
+        *buf = realloc(*buf, *cap);
+        if (*buf == NULL) {
+            perror("Out of memory");
+            exit(1);
+        }
+    }
+    for (size_t i = 0; i <= n; i++) {
+        (*buf)[*len + i] = s[i];
+    }
+    *len += n;
+}
+static unsigned __NHASH__(__NMAP__ *m, __NVAL__ k) {
+    unsigned h = 2166136261u;
+    if (m->kind == 'i') {
+        return (unsigned)k.i * 2654435761u;
+    }
+    for (const char *c = k.s; *c != '\0'; c++) {
+        h = (h ^ (unsigned char)*c) * 16777619u;
+    }
+    return h;
+}
+static bool __NKEYEQ__(__NMAP__ *m, __NVAL__ a, __NVAL__ b) {
+    return m->kind == 'i' ? a.i == b.i : strcmp(a.s, b.s) == 0;
+}
+static int __NMAP_FIND__(__NMAP__ *m, __NVAL__ k) {
+    if (m->cap == 0) {
+        return -1;
+    }
+    for (int i = m->buckets[__NHASH__(m, k) & (m->cap - 1)]; i != -1; i = m->entries[i].next) {
+        if (__NKEYEQ__(m, m->entries[i].key, k)) {
+            return i;
+        }
+    }
+    return -1;
+}
+/* Doubles the table and rebuilds the bucket chains, keeping the entries in order */
+static void __NMAP_GROW__(__NMAP__ *m) {
+    m->cap = m->cap == 0 ? 8 : m->cap * 2;
+    m->entries = realloc(m->entries, sizeof(__NENTRY__) * m->cap);
+    m->buckets = realloc(m->buckets, sizeof(int) * m->cap);
+    if (m->entries == NULL || m->buckets == NULL) {
+        perror("Out of memory");
+        exit(1);
+    }
+    for (int b = 0; b < m->cap; b++) {
+        m->buckets[b] = -1;
+    }
+    for (int i = 0; i < m->len; i++) {
+        unsigned b = __NHASH__(m, m->entries[i].key) & (m->cap - 1);
+        m->entries[i].next = m->buckets[b];
+        m->buckets[b] = i;
+    }
+}
+__NMAP__ *__NMAP_NEW__(char kind, char vkind) {
+    __NMAP__ *m = malloc(sizeof(__NMAP__));
+    if (m == NULL) {
+        perror("Out of memory");
+        exit(1);
+    }
+    m->len = 0;
+    m->cap = 0;
+    m->kind = kind;
+    m->vkind = vkind;
+    m->entries = NULL;
+    m->buckets = NULL;
+    return m;
+}
+__NMAP__ *__NMAP_OF__(char kind, char vkind, int n, ...) {
+    __NMAP__ *m = __NMAP_NEW__(kind, vkind);
+    va_list ap;
+    va_start(ap, n);
+    for (int i = 0; i < n; i++) {
+        __NVAL__ k = va_arg(ap, __NVAL__);
+        __NMAP_SET__(m, k, va_arg(ap, __NVAL__));
+    }
+    va_end(ap);
+    return m;
+}
+__NMAP__ *__NMAP_SET__(__NMAP__ *m, __NVAL__ k, __NVAL__ v) {
+    int i = __NMAP_FIND__(m, k);
+    if (i >= 0) {
+        /* The entry keeps its key, and the strings it no longer holds are freed */
+        if (m->kind == 's') {
+            free(k.s);
+        }
+        if (m->vkind == 's') {
+            free(m->entries[i].val.s);
+        }
+        m->entries[i].val = v;
+        return m;
+    }
+    if (m->len == m->cap) {
+        __NMAP_GROW__(m);
+    }
+    unsigned b = __NHASH__(m, k) & (m->cap - 1);
+    i = m->len++;
+    m->entries[i].key = k;
+    m->entries[i].val = v;
+    m->entries[i].next = m->buckets[b];
+    m->buckets[b] = i;
+    return m;
+}
+__NVAL__ *__NMAP_AT__(__NMAP__ *m, __NVAL__ k) {
+    int i = __NMAP_FIND__(m, k);
+    if (i < 0) {
+        if (m->kind == 'i') {
+            fprintf(stderr, "Runtime error :~ The key %d is not in the map\n", k.i);
+        } else {
+            fprintf(stderr, "Runtime error :~ The key \"%s\" is not in the map\n", k.s);
+        }
+        exit(1);
+    }
+    return &m->entries[i].val;
+}
+bool __NMAP_HAS__(__NMAP__ *m, __NVAL__ k) {
+    return __NMAP_FIND__(m, k) >= 0;
+}
+/* The last entry moves into the removed one's place, so removing never shifts the rest */
+__NMAP__ *__NMAP_REMOVE__(__NMAP__ *m, __NVAL__ k) {
+    int i = __NMAP_FIND__(m, k);
+    int last = m->len - 1;
+    int *link;
+    if (i < 0) {
+        return m;
+    }
+    link = &m->buckets[__NHASH__(m, k) & (m->cap - 1)];
+    while (*link != i) {
+        link = &m->entries[*link].next;
+    }
+    *link = m->entries[i].next;
+    if (m->kind == 's') {
+        free(m->entries[i].key.s);
+    }
+    if (m->vkind == 's') {
+        free(m->entries[i].val.s);
+    }
+    if (i != last) {
+        link = &m->buckets[__NHASH__(m, m->entries[last].key) & (m->cap - 1)];
+        while (*link != last) {
+            link = &m->entries[*link].next;
+        }
+        *link = i;
+        m->entries[i] = m->entries[last];
+    }
+    m->len--;
+    return m;
+}
+int __NMAP_LEN__(__NMAP__ *m) {
+    return m->len;
+}
+void __NMAP_FREE__(__NMAP__ **m) {
+    for (int i = 0; i < (*m)->len; i++) {
+        if ((*m)->kind == 's') {
+            free((*m)->entries[i].key.s);
+        }
+        if ((*m)->vkind == 's') {
+            free((*m)->entries[i].val.s);
+        }
+    }
+    free((*m)->entries);
+    free((*m)->buckets);
+    free(*m);
+}
+__NVAL__ __NMAP_KEY__(__NMAP__ *m, int i) {
+    return m->entries[i].key;
+}
+/*
+ * `kind` describes a value: i, f, b or s for plain values, `[` followed by the element kind
+ * for a list and `{` followed by the key kind and the value kind for a map
+ */
+static void __NSHOW_INTO__(char **buf, size_t *len, size_t *cap, __NVAL__ v, const char *kind) {
+    char num[64];
+    switch (kind[0]) {
+    case '[': {
+        __NLIST__ *l = v.p;
+        __NBUF_ADD__(buf, len, cap, "[");
+        for (int i = 0; i < l->len; i++) {
+            if (i > 0) {
+                __NBUF_ADD__(buf, len, cap, ", ");
+            }
+            __NSHOW_INTO__(buf, len, cap, l->items[i], kind + 1);
+        }
+        __NBUF_ADD__(buf, len, cap, "]");
+        break;
+    }
+    case '{': {
+        __NMAP__ *m = v.p;
+        __NBUF_ADD__(buf, len, cap, "{");
+        for (int i = 0; i < m->len; i++) {
+            if (i > 0) {
+                __NBUF_ADD__(buf, len, cap, ", ");
+            }
+            __NSHOW_INTO__(buf, len, cap, m->entries[i].key, kind + 1);
+            __NBUF_ADD__(buf, len, cap, ": ");
+            __NSHOW_INTO__(buf, len, cap, m->entries[i].val, kind + 2);
+        }
+        __NBUF_ADD__(buf, len, cap, "}");
+        break;
+    }
+    case 's':
+        __NBUF_ADD__(buf, len, cap, "\"");
+        __NBUF_ADD__(buf, len, cap, v.s);
+        __NBUF_ADD__(buf, len, cap, "\"");
+        break;
+    case 'f':
+        snprintf(num, sizeof(num), "%f", v.f);
+        __NBUF_ADD__(buf, len, cap, num);
+        break;
+    case 'b':
+        __NBUF_ADD__(buf, len, cap, v.b ? "true" : "false");
+        break;
+    default:
+        snprintf(num, sizeof(num), "%d", v.i);
+        __NBUF_ADD__(buf, len, cap, num);
+    }
+}
+/* Renders a list or a map the way the interpreter prints it */
+char *__NSHOW__(__NVAL__ v, const char *kind) {
+    char *buf = NULL;
+    size_t len = 0, cap = 0;
+    __NSHOW_INTO__(&buf, &len, &cap, v, kind);
+    return buf;
+}
 void __NCLRSCRN__() {
     #if defined(_WIN32) || defined(_WIN64)
         if (system("cls") == -1) {
@@ -27,10 +642,98 @@ void __NCLRSCRN__() {
 }
 "#;
 
-pub static NULIBCH: &'static str = r#"
+pub static NULIBCH: &str = r#"
 #ifndef NULIBC_H
 #define NULIBC_H
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+/* A single list element; which field is live follows from the list's element type */
+typedef union {
+    int i;
+    float f;
+    bool b;
+    char *s;
+    void *p;
+} __NVAL__;
+/* A growable array, shared by reference like lists in the interpreter */
+typedef struct {
+    int len;
+    int cap;
+    __NVAL__ *items;
+} __NLIST__;
+typedef struct {
+    __NVAL__ key;
+    __NVAL__ val;
+    int next;
+} __NENTRY__;
+/*
+ * A hash table keyed by strings (`kind` 's') or ints (`kind` 'i'), with values of the
+ * `__NVAL__` field `vkind`. Entries stay in insertion order and each bucket holds the index of
+ * the first entry of its chain, or -1
+ */
+typedef struct {
+    int len;
+    int cap;
+    char kind;
+    char vkind;
+    __NENTRY__ *entries;
+    int *buckets;
+} __NMAP__;
 int strcmp(const char *str1, const char *str2);
+/*
+ * Strings are NUL-terminated heap buffers of any length. Each one is owned by a single
+ * variable, field or element, which frees it when given a new one through __NSTR_SET__
+ */
+char *__NSTRDUP__(const char *s);
+char *__NSTRCAT__(const char *a, const char *b);
+char *__NSTR_SET__(char *old, char *s);
+/*
+ * Frees what a local variable owns when it goes out of scope, however the scope is left:
+ * `f` is __NFREE__ for a string, or the free function of a list, map or shape
+ */
+#define __NDROP__(f) __attribute__((cleanup(f)))
+void __NFREE__(char **s);
+int __NSTR_LEN__(const char *s);
+char *__NSTR_AT__(const char *s, int i);
+char *__NSTR_UPPER__(const char *s);
+char *__NSTR_LOWER__(const char *s);
+char *__NSTR_TRIM__(const char *s);
+bool __NSTR_HAS__(const char *s, const char *part);
+bool __NSTR_STARTS__(const char *s, const char *part);
+bool __NSTR_ENDS__(const char *s, const char *part);
+char *__NSTR_REPLACE__(const char *s, const char *from, const char *to);
+__NLIST__ *__NSTR_SPLIT__(const char *s, const char *sep);
+char *__NSTR_SLICE__(const char *s, int from, int to);
+int __NTO_INT__(const char *s);
+float __NTO_FLOAT__(const char *s);
+int __NSTEP__(int step);
+void __NWAIT__(long long ms);
+int __NFLOAT_INT__(float f);
+int __NDIV__(int a, int b);
+int __NMOD__(int a, int b);
+char *__NTAKEIN__();
+void *__NBOX__(const void *src, size_t n);
+__NLIST__ *__NLIST_NEW__(void);
+__NLIST__ *__NLIST_OF__(int n, ...);
+__NLIST__ *__NLIST_FILL__(int n, __NVAL__ v, bool str);
+__NLIST__ *__NLIST_PUSH__(__NLIST__ *l, __NVAL__ v);
+__NVAL__ __NLIST_POP__(__NLIST__ *l);
+__NVAL__ *__NLIST_AT__(__NLIST__ *l, int i);
+int __NLIST_LEN__(__NLIST__ *l);
+/* Frees a list, and with __NLIST_FREE_STRS__ its strings, but not the lists, maps or shapes in it */
+void __NLIST_FREE__(__NLIST__ **l);
+void __NLIST_FREE_STRS__(__NLIST__ **l);
+__NMAP__ *__NMAP_NEW__(char kind, char vkind);
+__NMAP__ *__NMAP_OF__(char kind, char vkind, int n, ...);
+__NMAP__ *__NMAP_SET__(__NMAP__ *m, __NVAL__ k, __NVAL__ v);
+__NVAL__ *__NMAP_AT__(__NMAP__ *m, __NVAL__ k);
+bool __NMAP_HAS__(__NMAP__ *m, __NVAL__ k);
+__NMAP__ *__NMAP_REMOVE__(__NMAP__ *m, __NVAL__ k);
+int __NMAP_LEN__(__NMAP__ *m);
+void __NMAP_FREE__(__NMAP__ **m);
+__NVAL__ __NMAP_KEY__(__NMAP__ *m, int i);
+char *__NSHOW__(__NVAL__ v, const char *kind);
 void __NCLRSCRN__();
 #endif // NULIBC_H
 "#;
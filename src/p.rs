@@ -1,49 +1,270 @@
 use crate::{
     err::{generr, ErrT},
+    expr::{parse_call_stmt, parse_expr, parse_expr_as, Expr},
     lex::{TokType, Tokens},
-    p2::{p2, Condition}, p3::p3,
+    module::{modules_for, Modules, MODULE_ITEMS},
+    p2::{is_escape, p2, tidy_body, ValueType},
+    p3::p3,
+    scope::{ScopeKind, Symbols},
+    typeck,
 };
 use colored::Colorize;
-use std::{collections::HashMap, process::exit};
+use std::{cell::RefCell, collections::HashMap, process::exit, rc::Rc};
 
 #[derive(Debug, PartialEq)]
 pub enum NST {
-    PRINT(String),
+    PRINT(Vec<PrintPart>),
     Var(Var),
     Input(String),
     VRDInput(String),
-    Func(String, Vec<String>, Vec<NST>),
+    /// Command name, parameters, declared return type (`None` for no value) and body.
+    Func(String, Vec<Param>, Option<ValueType>, Vec<Stmt>),
     NCLRSCRN,
     WAIT(u64),
-    NIF(Condition, Vec<NST>),
-    VarRD(String, VVal),
-    NWHILE(Condition, Vec<NST>),
+    /// `if` condition and body, any `else if` branches in order with the line of their
+    /// condition, and the optional `else` body.
+    NIF(
+        Expr,
+        Vec<Stmt>,
+        Vec<(usize, Expr, Vec<Stmt>)>,
+        Option<Vec<Stmt>>,
+    ),
+    VarRD(String, Expr),
+    NWHILE(Expr, Vec<Stmt>),
+    /// `for var in start..end step n`: loop variable, start, end, whether the end is
+    /// included (`..=`), optional step and body.
+    NFOR(String, Expr, Expr, bool, Option<Expr>, Vec<Stmt>),
+    /// `for x in xs`: loop variable, the list and body.
+    NEACH(String, Expr, Vec<Stmt>),
+    /// `xs[i] = value`: the list, the index and the new value.
+    SetIdx(Expr, Expr, Expr),
+    /// An expression run for its effect, such as `push(xs, 1)`.
+    Eval(Expr),
+    /// `shape Name { field: type ... }`: a record type and its fields.
+    Shape(String, Vec<Param>),
+    /// `p.field = value`: the record, the field's declaration and position, and the new value.
+    SetField(Expr, Param, usize, Expr),
+    /// `enum Name { A, B }`: an enumeration and its variants.
+    Enum(String, Vec<String>),
+    /// `match value { ... }`: the value, each arm's patterns and body, and the `_` arm.
+    Match(Expr, Vec<(Vec<Pat>, Vec<Stmt>)>, Option<Vec<Stmt>>),
+    /// Invocation of a command defined with `cmd` that gives no value, with its arguments. A
+    /// `call` statement to a command that gives one is an `Eval` of the call.
+    Call(String, Vec<Expr>),
+    /// `give <value>`: returns a value from the enclosing command.
+    Give(Expr),
+    /// Leaves the innermost loop.
+    Break,
+    /// Skips to the next iteration of the innermost loop.
+    Continue,
+}
+
+/// A statement and the line it starts on, which the type checker reports errors against.
+pub type Stmt = (usize, NST);
+
+/// One alternative of a `match` arm.
+#[derive(Debug, PartialEq)]
+pub enum Pat {
+    /// An int, string or enum variant compared for equality.
+    Val(Expr),
+    /// An inclusive range of ints, `1..=9` (`1..10` is stored as `1..=9`).
+    Range(i32, i32),
+}
+
+/// A piece of a print statement: plain text or an interpolated `{expression}`.
+#[derive(Debug, PartialEq)]
+pub enum PrintPart {
+    Text(String),
+    Expr(Expr),
+}
+
+/// Statement keywords handled by their own parser arm rather than as reassignments.
+pub const KEYWORDS: &[&str] = &[
+    "print", "println", "cls", "wait", "may", "cmd", "call", "give", "while", "for", "if", "else",
+    "break", "continue", "push", "pop", "remove", "shape", "enum", "match", "use", "const",
+];
+
+/// Signature of a command as seen by callers.
+#[derive(Debug, PartialEq, Clone)]
+pub struct CmdSig {
+    pub params: Vec<Param>,
+    pub ret: Option<ValueType>,
+    /// The name the command is emitted and called under: its own name, or `module.name` for
+    /// commands declared in a module, which no command in another file can be called.
+    pub link: String,
+}
+
+/// Parser state shared by every nested `parse_body` call.
+#[derive(Debug, Default)]
+pub struct Ctx {
+    /// Every command defined so far.
+    pub cmds: HashMap<String, CmdSig>,
+    /// The command whose body is currently being parsed, if any.
+    pub cur_cmd: Option<String>,
+    /// How many loops enclose the body currently being parsed.
+    pub loops: usize,
+    /// The fields of every shape declared so far.
+    pub shapes: HashMap<String, Vec<Param>>,
+    /// The variants of every enum declared so far.
+    pub enums: HashMap<String, Vec<String>>,
+    /// The name of the module being parsed, or `None` for the program's main file.
+    pub module: Option<String>,
+    /// The modules of the whole program, shared with the parsers of the files it uses.
+    pub modules: Rc<RefCell<Modules>>,
 }
 
 #[derive(Debug, PartialEq)]
 pub struct Var {
     pub name: String,
-    pub value: VVal,
+    /// The declared type, which is the value's own type unless an annotation widens it.
+    pub ty: ValueType,
+    pub value: Expr,
+}
+
+/// What the parser knows about a variable in scope.
+#[derive(Debug, Clone)]
+pub struct Binding {
+    pub ty: ValueType,
+    /// The value of a `const`, worked out while parsing and used in place of its name.
+    pub value: Option<Expr>,
+}
+
+impl Binding {
+    pub fn var(ty: ValueType) -> Binding {
+        Binding { ty, value: None }
+    }
+}
+
+/// A typed command parameter, e.g. `age:int`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Param {
+    pub name: String,
+    pub ty: ValueType,
 }
 
+/// A literal value, or the value of a variable at run time.
 #[derive(PartialEq, Clone, Debug)]
 pub enum VVal {
     Str(String),
     Int(i32),
     F(f32),
-    VarRef(String, String),
+    Bool(bool),
+    /// A list, shared by reference so that changes made through one name are seen by all.
+    List(Rc<RefCell<Vec<VVal>>>),
+    /// A map, shared by reference like a list.
+    Map(Rc<RefCell<NMap>>),
+    /// A shape's field values in declaration order, shared by reference like a list.
+    Shape(Rc<RefCell<Vec<VVal>>>),
+    /// An enum variant: its position in the declaration and its name.
+    Enum(usize, String),
+}
+
+/// The entries of a map in insertion order, with their positions indexed by key.
+///
+/// Removing an entry moves the last one into its place, which is exactly what the C runtime
+/// does, so both walk a map's keys in the same order.
+#[derive(PartialEq, Clone, Debug, Default)]
+pub struct NMap {
+    pub entries: Vec<(VVal, VVal)>,
+    index: HashMap<String, usize>,
+}
+
+impl NMap {
+    /// Map keys are strings or ints, and a map only holds one of the two, so their text is
+    /// enough to tell them apart.
+    fn slot(key: &VVal) -> String {
+        match key {
+            VVal::Str(s) => s.clone(),
+            VVal::Int(i) => i.to_string(),
+            _ => unreachable!("map keys are strings or ints"),
+        }
+    }
+
+    pub fn get(&self, key: &VVal) -> Option<&VVal> {
+        let i = *self.index.get(&NMap::slot(key))?;
+        Some(&self.entries[i].1)
+    }
+
+    pub fn set(&mut self, key: VVal, val: VVal) {
+        match self.index.get(&NMap::slot(&key)) {
+            Some(&i) => self.entries[i].1 = val,
+            None => {
+                self.index.insert(NMap::slot(&key), self.entries.len());
+                self.entries.push((key, val));
+            }
+        }
+    }
+
+    pub fn remove(&mut self, key: &VVal) {
+        let Some(i) = self.index.remove(&NMap::slot(key)) else {
+            return;
+        };
+        self.entries.swap_remove(i);
+        if let Some((moved, _)) = self.entries.get(i) {
+            self.index.insert(NMap::slot(moved), i);
+        }
+    }
 }
 
 pub fn parse(
     toks: &[Tokens],
     codes: &[&str],
     file: &str,
+    grammar: Option<&str>,
     errext: bool,
     errors: &mut Vec<ErrT>,
-) -> Vec<NST> {
-    let mut vars: HashMap<String, VVal> = HashMap::new();
+) -> Vec<Stmt> {
+    let mut ctx = Ctx {
+        modules: modules_for(file, grammar),
+        ..Ctx::default()
+    };
+    let nst = parse_body(toks, codes, file, errors, &Symbols::new(), &mut ctx, 1);
+    typeck::check(&nst, &ctx, errors);
+
+    if !errors.is_empty() {
+        report(errors, codes, file);
+        if errext {
+            exit(-1);
+        }
+    }
+
+    nst
+}
+
+/// Prints the errors found in one file, under that file's name.
+pub fn report(errors: &[ErrT], codes: &[&str], file: &str) {
+    eprintln!(
+        "{}{}\n{}",
+        "Errors detected in file: ".bold().red(),
+        file.yellow().bold(),
+        "+".repeat(100).red().dimmed()
+    );
+
+    for err in errors {
+        generr(err.clone(), codes);
+        eprintln!("{}", "─".repeat(100).red().dimmed());
+    }
+}
+
+/// Parses a block of tokens (the whole file or the body of a `cmd`, `if` or `while`).
+///
+/// `outer` holds the variables visible from the enclosing blocks with a fresh scope for this
+/// one already pushed, `ctx` the commands defined so far and `ln` is the line the block
+/// starts on, so errors inside nested bodies point at the right line.
+pub fn parse_body(
+    toks: &[Tokens],
+    codes: &[&str],
+    file: &str,
+    errors: &mut Vec<ErrT>,
+    outer: &Symbols<Binding>,
+    ctx: &mut Ctx,
+    ln: usize,
+) -> Vec<Stmt> {
+    let mut vars = outer.clone();
+    let mut body: Vec<Stmt> = Vec::new();
+    // The statements of the current line, which get its number once they are parsed
     let mut nst: Vec<NST> = Vec::new();
-    let mut ln: usize = 1;
+    let mut ln: usize = ln;
     let mut tok_iter = toks.iter().peekable();
 
     while let Some(tok) = tok_iter.next() {
@@ -51,6 +272,24 @@ pub fn parse(
             ln += 1;
         }
 
+        // Modules are only parsed for what they declare, so there is nowhere to run code
+        if tok.get_type() == TokType::CMD
+            && ctx.module.is_some()
+            && ctx.cur_cmd.is_none()
+            && !MODULE_ITEMS.contains(&tok.get_value())
+        {
+            errors.push(ErrT::BadUse(
+                ln,
+                format!(
+                    "A module can only declare commands, shapes and enums, so `{}` must go inside a `cmd`.",
+                    tok.get_value()
+                ),
+            ));
+            skip_line(&mut tok_iter, &mut ln);
+            continue;
+        }
+
+        let stmt_ln = ln;
         match (tok.get_type(), tok.get_value()) {
             (TokType::CMD, "print") | (TokType::CMD, "println") => {
                 let mut tp = String::new();
@@ -58,7 +297,7 @@ pub fn parse(
 
                 for pptok in tok_iter.by_ref() {
                     if pptok.get_type() != TokType::EOL {
-                        if pptok.get_value() == " " && !first_space_skipped {
+                        if pptok.get_type() == TokType::SPACE && !first_space_skipped {
                             first_space_skipped = true;
                             continue;
                         }
@@ -68,51 +307,27 @@ pub fn parse(
                     }
                 }
 
-                let mut vm = false;
-                let mut cvnm = String::new();
-                let mut cpti = tp.chars().peekable();
-
-                while let Some(c) = cpti.next() {
-                    match (c, cpti.peek()) {
-                        ('\\', Some('{')) | ('\\', Some('}')) => {
-                            cpti.next();
-                        }
-                        ('{', _) if !vm => {
-                            vm = true;
-                        }
-                        ('}', _) if vm => {
-                            vm = false;
-                            if vars.get(&cvnm).is_none()
-                                && !nst.iter().any(|x| matches!(x, NST::Input(n) if *n == cvnm))
-                            {
-                                errors.push(ErrT::VNF(ln, cvnm.clone()));
-                            }
-                            cvnm.clear();
-                        }
-                        (c, _) if vm => {
-                            cvnm.push(c);
-                        }
-                        _ => {}
-                    }
-                }
-
-                if tok.get_type() == TokType::CMD && tok.get_value() == "println" {
-                    tp.push_str("\\n");
+                let mut parts = parse_print(&tp, ln, &vars, ctx, errors);
+                if tok.get_value() == "println" {
+                    parts.push(PrintPart::Text("\n".to_string()));
                 }
-                nst.push(NST::PRINT(tp));
+                nst.push(NST::PRINT(parts));
+                ln += 1;
             }
             (TokType::CMD, "cls") => {
                 nst.push(NST::NCLRSCRN);
             }
-            (TokType::CMD, "may") => {
+            (TokType::CMD, kw @ ("may" | "const")) => {
                 let mut var_name = String::new();
                 let mut var_value = String::new();
+                // The optional annotation in `may xs: [int] = []`
+                let mut var_type = String::new();
                 let mut state = 0;
                 let mut round = 0;
                 let mut eqfound = false;
 
                 for vtok in tok_iter.by_ref() {
-                    if vtok.get_type() != TokType::SPACE {
+                    if vtok.get_type() != TokType::SPACE || state == 2 {
                         match state {
                             0 if vtok.get_type() == TokType::INSTR => {
                                 var_name = vtok.get_value().to_string();
@@ -126,25 +341,146 @@ pub fn parse(
                                 eqfound = true;
                                 round += 1;
                             }
+                            1 if vtok.get_type() == TokType::OP && vtok.get_value() == ":" => {
+                                state = 3;
+                            }
+                            3 if vtok.get_type() == TokType::OP && vtok.get_value() == "=" => {
+                                state = 2;
+                                eqfound = true;
+                                round += 1;
+                            }
+                            3 if vtok.get_type() != TokType::EOL => {
+                                var_type.push_str(vtok.get_value());
+                            }
                             _ if round == 0 && !eqfound => {
                                 errors.push(ErrT::EqNF(ln, var_name.clone()));
+                                if vtok.get_type() == TokType::EOL {
+                                    ln += 1;
+                                } else {
+                                    skip_line(&mut tok_iter, &mut ln);
+                                }
                                 break;
                             }
                             2 if vtok.get_type() != TokType::EOL => {
                                 var_value.push_str(vtok.get_value());
                             }
                             2 => {
-                                let vval = parse_var_value(&var_value, ln, &mut vars, errors, &nst);
-
-                                if vval != VVal::Str("__TAKEININPUT__".to_string()) {
-                                    vars.insert(var_name.clone(), vval.clone());
-                                    nst.push(NST::Var(Var {
-                                        name: var_name.clone(),
-                                        value: vval,
-                                    }));
+                                let ty = if var_type.is_empty() {
+                                    None
                                 } else {
-                                    nst.push(NST::Input(var_name.clone()));
+                                    let ty = ValueType::from_name(&var_type, ctx);
+                                    if ty.is_none() {
+                                        errors.push(ErrT::UnknownType(
+                                            ln,
+                                            format!("the variable `{}`", var_name),
+                                            var_type.trim().to_string(),
+                                        ));
+                                    }
+                                    ty
+                                };
+                                let value = parse_expr_as(
+                                    var_value.trim(),
+                                    ty.as_ref(),
+                                    ln,
+                                    &vars,
+                                    ctx,
+                                    errors,
+                                );
+                                if vars.global(&var_name) {
+                                    errors.push(global_err(ln, &var_name));
+                                } else if let Some(err) = type_name_err(ln, &var_name, ctx) {
+                                    errors.push(err);
+                                } else if ctx.cmds.contains_key(&var_name) {
+                                    errors.push(ErrT::BadScope(
+                                        ln,
+                                        format!(
+                                            "`{}` is already the name of a command, pick another name.",
+                                            var_name
+                                        ),
+                                    ));
+                                } else if vars.declared_here(&var_name) {
+                                    let hint = match vars.get(&var_name) {
+                                        Some(Binding { value: Some(_), .. }) => {
+                                            "pick another name".to_string()
+                                        }
+                                        _ => format!("change it with `{} = ...` instead", var_name),
+                                    };
+                                    errors.push(ErrT::BadScope(
+                                        ln,
+                                        format!(
+                                            "`{}` is already declared in this block, {}.",
+                                            var_name, hint
+                                        ),
+                                    ));
                                 }
+                                let value = match (value, &ty) {
+                                    (Some(v), _)
+                                        if v.uses(&var_name) && !vars.declared_here(&var_name) =>
+                                    {
+                                        errors.push(shadow_err(ln, &var_name));
+                                        None
+                                    }
+                                    (value, _) => value,
+                                };
+                                match value {
+                                    // A constant leaves nothing to run, its uses are replaced
+                                    Some(value) if kw == "const" => {
+                                        if let Some(ty) = &ty {
+                                            typeck::check_assign(&var_name, ty, &value, ln, errors);
+                                        }
+                                        match value.fold() {
+                                            Ok(folded) => {
+                                                let folded = match (&ty, folded) {
+                                                    (
+                                                        Some(ValueType::Float),
+                                                        Expr::Lit(VVal::Int(i)),
+                                                    ) => Expr::Lit(VVal::F(i as f32)),
+                                                    (_, folded) => folded,
+                                                };
+                                                vars.declare(
+                                                    &var_name,
+                                                    Binding {
+                                                        ty: folded.ty(),
+                                                        value: Some(folded),
+                                                    },
+                                                );
+                                            }
+                                            Err(why) => {
+                                                errors.push(ErrT::BadConst(
+                                                ln,
+                                                format!(
+                                                    "The value of `{}` must be known while compiling, but {}",
+                                                    var_name, why
+                                                ),
+                                            ));
+                                                vars.declare(&var_name, Binding::var(value.ty()));
+                                            }
+                                        }
+                                    }
+                                    Some(Expr::Input)
+                                        if matches!(ty, None | Some(ValueType::Str)) =>
+                                    {
+                                        vars.declare(&var_name, Binding::var(ValueType::Str));
+                                        nst.push(NST::Input(var_name.clone()));
+                                    }
+                                    Some(value) => {
+                                        let ty = ty.unwrap_or_else(|| value.ty());
+                                        vars.declare(&var_name, Binding::var(ty.clone()));
+                                        nst.push(NST::Var(Var {
+                                            name: var_name.clone(),
+                                            ty,
+                                            value,
+                                        }));
+                                    }
+                                    // Still declared so later uses don't report it as missing
+                                    None => {
+                                        vars.declare(
+                                            &var_name,
+                                            Binding::var(ty.unwrap_or(ValueType::Int)),
+                                        );
+                                    }
+                                }
+                                ln += 1;
                                 break;
                             }
                             _ => {}
@@ -153,39 +489,15 @@ pub fn parse(
                 }
             }
             (TokType::CMD, "wait") => {
-                fn convert_to_ms(time_str: &str, errors: &mut Vec<ErrT>, line: usize) -> u64 {
-                    let ogt = &time_str;
-                    let time_str = time_str.to_ascii_lowercase();
-                    let time_str = time_str.trim();
-                    if time_str.ends_with("ms") {
-                        let (num_str, _) = time_str.split_at(time_str.len() - 2);
-                        let num: u64 = num_str.parse().unwrap_or(0);
-                        num
-                    } else if time_str.ends_with("s") {
-                        let (num_str, _) = time_str.split_at(time_str.len() - 1);
-                        let num: u64 = num_str.parse().unwrap_or(0);
-                        num * 1000
-                    } else if time_str.ends_with("m") {
-                        let (num_str, _) = time_str.split_at(time_str.len() - 1);
-                        let num: u64 = num_str.parse().unwrap_or(0);
-                        num * 60 * 1000
-                    } else if time_str.ends_with("hr") {
-                        let (num_str, _) = time_str.split_at(time_str.len() - 2);
-                        let num: u64 = num_str.parse().unwrap_or(0);
-                        num * 60 * 60 * 1000
-                    } else {
-                        errors.push(ErrT::InVTimeVal(line, ogt.to_string()));
-                        0
-                    }
-                }
-
                 let mut a = String::new();
                 for tok in tok_iter.by_ref() {
                     if tok.get_type() == TokType::EOL {
                         //println!("[DEBUG] og wait time :~ {}", a);
-                        let time_in_ms = convert_to_ms(&a, errors, ln);
+                        let time_in_ms = wait_ms(&a, ln, &vars, ctx, errors);
                         //println!("[DEBUG] wait for :~ {} => {} ms", a, time_in_ms);
                         nst.push(NST::WAIT(time_in_ms));
+                        ln += 1;
+                        break;
                     } else {
                         a.push_str(tok.get_value());
                     }
@@ -193,28 +505,46 @@ pub fn parse(
             }
             (TokType::CMD, "cmd") => {
                 let mut bc = 0;
-                let mut name = String::new();
-                let mut args = Vec::new();
+                let mut header = String::new();
+                let mut type_depth = 0usize;
                 let mut body = Vec::new();
+                let start = ln;
+                let mut body_start = ln;
+                let mut escaped = false;
 
                 for ctok in tok_iter.by_ref() {
-                    println!("bc : {} | tok : {:?}", bc, ctok);
                     if ctok.get_type() == TokType::EOL {
+                        ln += 1;
+                        if bc > 0 {
+                            body.push(ctok.clone());
+                        }
                         continue;
                     }
                     if bc == 0 {
-                        if ctok.get_type() == TokType::INSTR {
-                            if name.is_empty() {
-                                name = ctok.get_value().to_string();
-                            } else {
-                                args.push(ctok.get_value().to_string());
+                        // A `{` inside the parameters or right after a `:` opens a map type
+                        // such as `{str: int}` rather than the body
+                        let in_type = type_depth > 0
+                            || header.matches('(').count() > header.matches(')').count()
+                            || header.trim_end().ends_with([':', '[']);
+                        match (ctok.get_type(), ctok.get_value()) {
+                            (TokType::OP, "{") if !in_type => {
+                                bc += 1;
+                                body_start = ln;
                             }
-                        }
-                        if ctok.get_type() == TokType::OP && ctok.get_value() == "{" {
-                            bc += 1;
+                            (TokType::OP, v) => {
+                                match v {
+                                    "{" => type_depth += 1,
+                                    "}" => type_depth = type_depth.saturating_sub(1),
+                                    _ => {}
+                                }
+                                header.push_str(v);
+                            }
+                            (_, v) => header.push_str(v),
                         }
                     } else {
-                        if ctok.get_type() == TokType::OP {
+                        let after_escape = escaped;
+                        escaped = is_escape(ctok, escaped);
+                        if ctok.get_type() == TokType::OP && !after_escape {
                             if ctok.get_value() == "{" {
                                 bc += 1;
                             } else if ctok.get_value() == "}" {
@@ -227,78 +557,456 @@ pub fn parse(
                         body.push(ctok.clone());
                     }
                 }
-                let func_body = parse(&body, codes, file, false, errors);
-                nst.push(NST::Func(name, args, func_body));
+                // `name (params): type`, where both the parameters and the type are optional
+                let (name, params, ret) = match header.split_once('(') {
+                    Some((name, rest)) => {
+                        let (params, ret) = rest.split_once(')').unwrap_or((rest, ""));
+                        (name, Some(params.to_string()), ret)
+                    }
+                    None => match header.split_once(':') {
+                        Some((name, ret)) => (name, None, ret),
+                        None => (header.as_str(), None, ""),
+                    },
+                };
+                let name = name.trim().to_string();
+                let ret = ret.trim().trim_start_matches(':').trim();
+                let ret = if ret.is_empty() {
+                    None
+                } else {
+                    let ty = ValueType::from_name(ret, ctx);
+                    if ty.is_none() {
+                        errors.push(ErrT::UnknownType(
+                            start,
+                            format!("the return value of `{}`", name),
+                            ret.to_string(),
+                        ));
+                    }
+                    ty
+                };
+                if bc != 0 {
+                    errors.push(ErrT::UnmatchedParen(start, format!("cmd {}", name)));
+                }
+                if !vars.at_top() {
+                    errors.push(ErrT::BadScope(
+                        start,
+                        format!(
+                            "The command `{}` is declared inside another body, commands can only be declared at the top level of a file.",
+                            name
+                        ),
+                    ));
+                }
+
+                // Parameters may also be given on the first line of the body: `(name:str)`
+                let params = params.or_else(|| {
+                    let first = body
+                        .iter()
+                        .position(|t| !matches!(t.get_type(), TokType::SPACE | TokType::EOL))?;
+                    if body[first].get_value() != "(" {
+                        return None;
+                    }
+                    let close = body[first..].iter().position(|t| t.get_value() == ")")? + first;
+                    let params: String = body[first + 1..close]
+                        .iter()
+                        .map(|t| t.get_value())
+                        .collect();
+                    body.drain(first..=close);
+                    Some(params)
+                });
+                tidy_body(&mut body);
+                let args =
+                    parse_params(&params.unwrap_or_default(), "parameter", start, errors, ctx);
+
+                let link = match &ctx.module {
+                    Some(ns) => format!("{}.{}", ns, name),
+                    None => name.clone(),
+                };
+                // Registered before the body is parsed so commands can call themselves
+                ctx.cmds.insert(
+                    name.clone(),
+                    CmdSig {
+                        params: args.clone(),
+                        ret: ret.clone(),
+                        link: link.clone(),
+                    },
+                );
+                // Commands are emitted as separate C functions, so their scope starts with only
+                // their parameters
+                let mut locals = vars.nested(ScopeKind::Cmd);
+                for p in &args {
+                    if locals.global(&p.name) {
+                        errors.push(global_err(start, &p.name));
+                    }
+                    locals.declare(&p.name, Binding::var(p.ty.clone()));
+                }
+                if vars.contains_key(&name) {
+                    errors.push(ErrT::BadScope(
+                        start,
+                        format!(
+                            "`{}` is already the name of a variable, pick another name for the command.",
+                            name
+                        ),
+                    ));
+                } else if let Some(err) = type_name_err(start, &name, ctx) {
+                    errors.push(err);
+                }
+                let outer_cmd = ctx.cur_cmd.replace(name.clone());
+                // A loop around the definition does not reach into the command's body
+                let outer_loops = std::mem::take(&mut ctx.loops);
+                let func_body = parse_body(&body, codes, file, errors, &locals, ctx, body_start);
+                ctx.cur_cmd = outer_cmd;
+                ctx.loops = outer_loops;
+
+                if ret.is_some() && !gives(&func_body) {
+                    errors.push(ErrT::BadGive(
+                        start,
+                        format!(
+                            "The command `{}` declares a return type but can finish without giving a value, end every path through it with `give`.",
+                            name
+                        ),
+                    ));
+                }
+                nst.push(NST::Func(link, args, ret, func_body));
+            }
+            (TokType::CMD, "call") => {
+                let mut text = String::new();
+                for ctok in tok_iter.by_ref() {
+                    if ctok.get_type() == TokType::EOL {
+                        break;
+                    }
+                    text.push_str(ctok.get_value());
+                }
+
+                if let Some((name, args, ret)) = parse_call_stmt(&text, ln, &vars, ctx, errors) {
+                    // A value given to a `call` statement is dropped, like any other unused
+                    // expression, which lets the compiled program free it
+                    nst.push(match ret {
+                        Some(ty) => NST::Eval(Expr::Call(name, args, ty)),
+                        None => NST::Call(name, args),
+                    });
+                }
+                ln += 1;
+            }
+            (TokType::CMD, "give") => {
+                let mut text = String::new();
+                for gtok in tok_iter.by_ref() {
+                    if gtok.get_type() == TokType::EOL {
+                        break;
+                    }
+                    text.push_str(gtok.get_value());
+                }
+
+                let ret = ctx
+                    .cur_cmd
+                    .as_ref()
+                    .and_then(|c| ctx.cmds.get(c))
+                    .map(|sig| sig.ret.clone());
+                let expect = ret.clone().flatten();
+                let value =
+                    match parse_expr_as(text.trim(), expect.as_ref(), ln, &vars, ctx, errors) {
+                        Some(value) => value,
+                        None => {
+                            ln += 1;
+                            continue;
+                        }
+                    };
+                match ret {
+                    None => errors.push(ErrT::BadGive(
+                        ln,
+                        "`give` can only be used inside a command body.".to_string(),
+                    )),
+                    Some(None) => errors.push(ErrT::BadGive(
+                        ln,
+                        format!(
+                            "The command `{}` has no return type, so it cannot give a value.",
+                            ctx.cur_cmd.clone().unwrap_or_default()
+                        ),
+                    )),
+                    // Whether the value fits the return type is up to `typeck`
+                    Some(Some(_)) => {}
+                }
+                nst.push(NST::Give(value));
+                ln += 1;
             }
             _ => {
-                let b = p2(tok, &mut tok_iter, codes, errors, &mut nst, &mut ln, &vars, file);
-                if !b{
-                    p3(tok, &mut tok_iter, codes, errors, &mut nst, &mut ln, &vars, file);
+                let b = p2(
+                    tok,
+                    &mut tok_iter,
+                    codes,
+                    errors,
+                    &mut nst,
+                    &mut ln,
+                    &vars,
+                    ctx,
+                    file,
+                );
+                if !b {
+                    p3(
+                        tok,
+                        &mut tok_iter,
+                        codes,
+                        errors,
+                        &mut nst,
+                        &mut ln,
+                        &vars,
+                        ctx,
+                        file,
+                    );
                 }
             }
         }
+        body.extend(nst.drain(..).map(|stmt| (stmt_ln, stmt)));
     }
 
-    if !errors.is_empty() {
-        eprintln!(
-            "{}{}\n{}",
-            "Errors detected in file: ".bold().red(),
-            file.yellow().bold(),
-            "+".repeat(100).red().dimmed()
-        );
-
-        for err in errors {
-            generr(err.clone(), &codes.to_vec());
-            eprintln!("{}", "─".repeat(100).red().dimmed());
+    body
+}
+
+/// The error for a variable or command named after a shape or an enum, whose C type would be
+/// declared again under the same name.
+fn type_name_err(ln: usize, name: &str, ctx: &Ctx) -> Option<ErrT> {
+    let kind = if ctx.shapes.contains_key(name) {
+        "a shape"
+    } else if ctx.enums.contains_key(name) {
+        "an enum"
+    } else {
+        return None;
+    };
+    Some(ErrT::BadScope(
+        ln,
+        format!(
+            "`{}` is already the name of {}, pick another name.",
+            name, kind
+        ),
+    ))
+}
+
+/// The error for a command declaring a parameter or variable under the name of a global,
+/// which would leave the global out of reach for the rest of the command.
+pub fn global_err(ln: usize, name: &str) -> ErrT {
+    ErrT::BadScope(
+        ln,
+        format!(
+            "`{}` is a global variable, so a command cannot declare its own `{}`, pick another name.",
+            name, name
+        ),
+    )
+}
+
+/// The error for a declaration whose value reads the variable it shadows, which C would
+/// resolve to the new, not yet initialised, variable.
+pub fn shadow_err(ln: usize, name: &str) -> ErrT {
+    ErrT::BadScope(
+        ln,
+        format!(
+            "`{}` shadows a variable of an enclosing block, so its value cannot use that variable, pick another name.",
+            name
+        ),
+    )
+}
+
+/// Checks whether every way through a command body ends in a `give`.
+///
+/// An `if` only counts with an `else`, and a `match` with a `_` arm or over an enum, whose
+/// arms cover every variant. A loop never counts, as its body may not run at all.
+fn gives(body: &[Stmt]) -> bool {
+    body.iter().any(|(_, n)| match n {
+        NST::Give(_) => true,
+        NST::NIF(_, body, elifs, Some(else_body)) => {
+            gives(body) && elifs.iter().all(|(_, _, b)| gives(b)) && gives(else_body)
         }
-        if errext {
-            exit(-1);
+        NST::Match(value, arms, default) => {
+            arms.iter().all(|(_, b)| gives(b))
+                && match default {
+                    Some(b) => gives(b),
+                    None => matches!(value.ty(), ValueType::Enum(_)),
+                }
+        }
+        _ => false,
+    })
+}
+
+/// Converts a `wait` duration such as `2s`, `500ms` or `DELAY m` to milliseconds. The amount
+/// is a whole number or a constant, and the unit one of `ms`, `s`, `m` or `hr`.
+fn wait_ms(
+    text: &str,
+    ln: usize,
+    vars: &Symbols<Binding>,
+    ctx: &Ctx,
+    errors: &mut Vec<ErrT>,
+) -> u64 {
+    let text = text.trim();
+    let lower = text.to_ascii_lowercase();
+    let Some((unit, scale)) = [
+        ("ms", 1),
+        ("s", 1000),
+        ("m", 60 * 1000),
+        ("hr", 60 * 60 * 1000),
+    ]
+    .into_iter()
+    .find(|(unit, _)| lower.ends_with(unit)) else {
+        errors.push(ErrT::InVTimeVal(ln, text.to_string()));
+        return 0;
+    };
+    let amount = text[..text.len() - unit.len()].trim();
+    let n = match amount.parse::<u64>() {
+        Ok(n) => Some(n),
+        // No amount, or a number too large to count, is no expression either
+        Err(_) if amount.chars().all(|c| c.is_ascii_digit()) => None,
+        Err(_) => match parse_expr(amount, ln, vars, ctx, errors).map(|e| e.fold()) {
+            Some(Ok(Expr::Lit(VVal::Int(n)))) if n >= 0 => Some(n as u64),
+            // A missing variable has been reported already
+            None => return 0,
+            Some(_) => None,
+        },
+    };
+    // The C code sleeps for the time in milliseconds, which must fit in a `long long`
+    match n
+        .and_then(|n| n.checked_mul(scale))
+        .filter(|&ms| ms <= i64::MAX as u64)
+    {
+        Some(ms) => ms,
+        None => {
+            errors.push(ErrT::InVTimeVal(ln, text.to_string()));
+            0
         }
     }
+}
 
-    nst
+/// Parses a command parameter list such as `name:str, age:int` into typed parameters, or the
+/// fields of a shape, as `kind` tells.
+pub fn parse_params(
+    params: &str,
+    kind: &str,
+    ln: usize,
+    errors: &mut Vec<ErrT>,
+    ctx: &Ctx,
+) -> Vec<Param> {
+    let mut out = Vec::new();
+    for p in params.split(',').filter(|p| !p.trim().is_empty()) {
+        let (name, ty) = p.split_once(':').unwrap_or((p, ""));
+        match ValueType::from_name(ty.trim(), ctx) {
+            Some(ty) => out.push(Param {
+                name: name.trim().to_string(),
+                ty,
+            }),
+            None => errors.push(ErrT::UnknownType(
+                ln,
+                format!("the {} `{}`", kind, name.trim()),
+                ty.trim().to_string(),
+            )),
+        }
+    }
+    out
+}
+
+/// Consumes the rest of the current line, including its EOL.
+pub fn skip_line(tok_iter: &mut std::iter::Peekable<std::slice::Iter<'_, Tokens>>, ln: &mut usize) {
+    for tok in tok_iter.by_ref() {
+        if tok.get_type() == TokType::EOL {
+            *ln += 1;
+            break;
+        }
+    }
 }
 
-fn parse_var_value(
-    var_value: &str,
+/// Splits the text of a print statement into literal text and `{expression}` parts.
+///
+/// Escapes (`\n`, `\t`, `\{`, ...) are resolved here, so the parts hold the exact text to print.
+fn parse_print(
+    text: &str,
     ln: usize,
-    vars: &mut HashMap<String, VVal>,
+    vars: &Symbols<Binding>,
+    ctx: &Ctx,
     errors: &mut Vec<ErrT>,
-    nst: &Vec<NST>,
-) -> VVal {
-    if (var_value.starts_with('"') && var_value.ends_with('"'))
-        || (var_value.starts_with("'") && var_value.ends_with("'"))
-    {
-        let value = var_value
-            .trim()
-            .trim_start_matches('"')
-            .trim_start_matches("'")
-            .trim_end_matches('"')
-            .trim_end_matches("'");
-        VVal::Str(value.to_string())
-    } else if let Ok(val) = var_value.parse::<i32>() {
-        VVal::Int(val)
-    } else if let Ok(val) = var_value.parse::<f32>() {
-        VVal::F(val)
-    } else if let Some(v) = vars.get(var_value) {
-        VVal::VarRef(
-            var_value.to_string(),
-            match v {
-                VVal::Int(_) => "i".to_string(),
-                VVal::F(_) => "f".to_string(),
-                VVal::Str(_) => "s".to_string(),
-                VVal::VarRef(_, t) => t.to_string(),
+) -> Vec<PrintPart> {
+    let mut parts = Vec::new();
+    let mut lit = String::new();
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('n') => lit.push('\n'),
+                Some('t') => lit.push('\t'),
+                Some(e) => lit.push(e),
+                None => lit.push('\\'),
             },
-        )
-    } else if nst
-        .iter()
-        .any(|x| matches!(x, NST::Input(n) if *n == var_value))
-    {
-        VVal::VarRef("".to_string(), "s".to_string())
-    } else if var_value == "takein()" {
-        VVal::Str("__TAKEININPUT__".to_string())
-    } else {
-        errors.push(ErrT::InValidVarVal(ln, var_value.to_string()));
-        VVal::Str("ERR_VAR_NOT_FOUND___!".to_string())
+            '{' => {
+                // Braces nest so calls such as `{call add {1, 2}}` stay in one expression
+                let mut src = String::new();
+                let mut depth = 1;
+                let mut quote = None;
+                while let Some(n) = chars.next() {
+                    match (n, quote) {
+                        // An escaped quote or brace inside a string doesn't end or open anything
+                        ('\\', Some(_)) => {
+                            src.push(n);
+                            if let Some(e) = chars.next() {
+                                src.push(e);
+                            }
+                            continue;
+                        }
+                        ('"' | '\'', None) => quote = Some(n),
+                        (n, Some(q)) if n == q => quote = None,
+                        ('{', None) => depth += 1,
+                        ('}', None) => depth -= 1,
+                        _ => {}
+                    }
+                    if depth == 0 {
+                        break;
+                    }
+                    src.push(n);
+                }
+                if depth != 0 {
+                    errors.push(ErrT::Unprintable(
+                        ln,
+                        format!(
+                            "`{{{}` is never closed, add the missing `}}` or write `\\{{` to print the brace itself",
+                            src
+                        ),
+                    ));
+                    continue;
+                }
+                if src.trim().is_empty() {
+                    errors.push(ErrT::Unprintable(
+                        ln,
+                        "`{}` has nothing inside to print, write `\\{\\}` to print the braces themselves"
+                            .to_string(),
+                    ));
+                    continue;
+                }
+                if !lit.is_empty() {
+                    parts.push(PrintPart::Text(std::mem::take(&mut lit)));
+                }
+                // A value the expression parser cannot read is reported by it as a bad
+                // assignment, but nothing is assigned here
+                let mut found = Vec::new();
+                if let Some(e) = parse_expr(&src, ln, vars, ctx, &mut found) {
+                    parts.push(PrintPart::Expr(e));
+                }
+                errors.extend(found.into_iter().map(|err| match err {
+                    ErrT::InValidVarVal(_, value) if value == src.trim() => {
+                        ErrT::Unprintable(ln, format!("`{{{}}}` is not a valid expression", value))
+                    }
+                    ErrT::InValidVarVal(_, value) => ErrT::Unprintable(
+                        ln,
+                        format!(
+                            "`{{{}}}` is not a valid expression, `{}` is not a value",
+                            src.trim(),
+                            value
+                        ),
+                    ),
+                    err => err,
+                }));
+            }
+            '}' => errors.push(ErrT::Unprintable(
+                ln,
+                "`}` closes no `{`, write `\\}` to print the brace itself".to_string(),
+            )),
+            c => lit.push(c),
+        }
+    }
+    if !lit.is_empty() {
+        parts.push(PrintPart::Text(lit));
     }
+    parts
 }
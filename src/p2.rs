@@ -1,20 +1,11 @@
 use crate::{
     err::ErrT,
+    expr::{parse_expr, parse_expr_as, Expr},
     lex::{TokType, Tokens},
-    p::{parse, VVal, NST},
+    p::{parse_body, Binding, Ctx, NMap, Param, VVal, KEYWORDS, NST},
+    scope::{ScopeKind, Symbols},
 };
-use std::collections::HashMap;
-
-#[derive(Debug, Clone, PartialEq)]
-pub struct Condition {
-    pub left: String,
-    pub operator: String,
-    pub right: String,
-    pub left_type: ValueType,
-    pub right_type: ValueType,
-    pub c_operator: String,
-    pub c_code: String,
-}
+use std::{cell::RefCell, fmt, rc::Rc};
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum ValueType {
@@ -22,8 +13,95 @@ pub enum ValueType {
     Float,
     Str,
     Bool,
+    /// A list of values of the element type, written `[int]`.
+    List(Box<ValueType>),
+    /// A map from keys of the first type to values of the second, written `{str: int}`.
+    Map(Box<ValueType>, Box<ValueType>),
+    /// A record declared with `shape`, by name.
+    Shape(String),
+    /// An enumeration declared with `enum`, by name.
+    Enum(String),
 }
 
+impl ValueType {
+    /// Maps a type name used in Neit source (`int`, `float`, `str`, `bool`, `[int]`,
+    /// `{str: int}` or the name of a shape or enum declared so far) to its type.
+    pub fn from_name(name: &str, ctx: &Ctx) -> Option<ValueType> {
+        match name.trim() {
+            "int" => Some(ValueType::Int),
+            "float" => Some(ValueType::Float),
+            "str" => Some(ValueType::Str),
+            "bool" => Some(ValueType::Bool),
+            name if ctx.shapes.contains_key(name) => Some(ValueType::Shape(name.to_string())),
+            name if ctx.enums.contains_key(name) => Some(ValueType::Enum(name.to_string())),
+            name if name.starts_with('{') => {
+                let (key, val) = name.strip_prefix('{')?.strip_suffix('}')?.split_once(':')?;
+                let key = ValueType::from_name(key, ctx).filter(ValueType::is_key)?;
+                let val = ValueType::from_name(val, ctx)?;
+                Some(ValueType::Map(Box::new(key), Box::new(val)))
+            }
+            name => {
+                let elem = name.strip_prefix('[')?.strip_suffix(']')?;
+                Some(ValueType::List(Box::new(ValueType::from_name(elem, ctx)?)))
+            }
+        }
+    }
+
+    /// Whether values of this type can appear in a print statement. Shapes have no printed
+    /// form, and lists and maps only print plain values.
+    pub fn printable(&self) -> bool {
+        fn plain(ty: &ValueType) -> bool {
+            match ty {
+                ValueType::Shape(_) | ValueType::Enum(_) => false,
+                ValueType::List(elem) => plain(elem),
+                ValueType::Map(key, val) => plain(key) && plain(val),
+                _ => true,
+            }
+        }
+        matches!(self, ValueType::Enum(_)) || plain(self)
+    }
+
+    /// Whether values of this type can be used as map keys.
+    pub fn is_key(&self) -> bool {
+        matches!(self, ValueType::Str | ValueType::Int)
+    }
+
+    /// Whether a value of type `other` can be stored where this type is expected.
+    pub fn accepts(&self, other: &ValueType) -> bool {
+        self == other || (*self == ValueType::Float && *other == ValueType::Int)
+    }
+
+    /// A placeholder value of this type, used to register typed names with the parser.
+    pub fn zero(&self) -> VVal {
+        match self {
+            ValueType::Int => VVal::Int(0),
+            ValueType::Bool => VVal::Bool(false),
+            ValueType::Float => VVal::F(0.0),
+            ValueType::Str => VVal::Str(String::new()),
+            ValueType::List(_) => VVal::List(Rc::new(RefCell::new(Vec::new()))),
+            ValueType::Map(..) => VVal::Map(Rc::new(RefCell::new(NMap::default()))),
+            // Never read: a shape value only comes from constructing one
+            ValueType::Shape(_) => VVal::Shape(Rc::new(RefCell::new(Vec::new()))),
+            ValueType::Enum(_) => VVal::Enum(0, String::new()),
+        }
+    }
+}
+
+impl fmt::Display for ValueType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ValueType::Int => "int",
+            ValueType::Float => "float",
+            ValueType::Str => "str",
+            ValueType::Bool => "bool",
+            ValueType::List(elem) => return write!(f, "[{}]", elem),
+            ValueType::Map(key, val) => return write!(f, "{{{}: {}}}", key, val),
+            ValueType::Shape(name) | ValueType::Enum(name) => name.as_str(),
+        })
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn p2(
     toks: &Tokens,
     tok_iter: &mut std::iter::Peekable<std::slice::Iter<'_, Tokens>>,
@@ -31,415 +109,390 @@ pub fn p2(
     errors: &mut Vec<ErrT>,
     nst: &mut Vec<NST>,
     ln: &mut usize,
-    vars: &HashMap<String, VVal>,
+    vars: &Symbols<Binding>,
+    ctx: &mut Ctx,
     file: &str,
 ) -> bool {
     match (toks.get_type(), toks.get_value()) {
         (TokType::CMD, "if") => {
-            let mut cond = String::new();
-            let mut body_tokens = Vec::new();
-            let mut in_parentheses = false;
-            let mut brace_count = 0;
+            let cond = match collect_cond(tok_iter, codes, errors, ln) {
+                Some(cond) => cond,
+                None => return true,
+            };
 
-            // Parse condition inside parentheses
-            while let Some(tok) = tok_iter.next() {
-                match (tok.get_type(), tok.get_value()) {
-                    (TokType::OP, "(") if !in_parentheses => {
-                        in_parentheses = true;
-                    }
-                    (TokType::EOL, _) => {
-                        *ln += 1;
-                    }
-                    (TokType::OP, ")") if in_parentheses => {
-                        in_parentheses = false;
-                        break; // Exit condition parsing
-                    }
-                    (TokType::SPACE, _) => {
-                        continue; // Ignore spaces inside condition
-                    }
-                    (_, _) if in_parentheses => {
-                        cond.push_str(tok.get_value());
+            let condition = match parse_expr(&cond, *ln, vars, ctx, errors) {
+                Some(cond) => cond,
+                None => return true,
+            };
+
+            let (body_tokens, body_ln) = match collect_body(tok_iter, errors, ln, "if") {
+                Some(body) => body,
+                None => return true,
+            };
+
+            // Parse the body tokens
+            let body = parse_body(
+                &body_tokens,
+                codes,
+                file,
+                errors,
+                &vars.nested(ScopeKind::Block),
+                ctx,
+                body_ln,
+            );
+
+            let mut elifs = Vec::new();
+            let mut else_body = None;
+            while next_is_else(tok_iter) {
+                // Consume up to and including the `else` keyword
+                for tok in tok_iter.by_ref() {
+                    match tok.get_type() {
+                        TokType::EOL => *ln += 1,
+                        TokType::SPACE => {}
+                        _ => break,
                     }
-                    _ => {
-                        
-                        errors.push(ErrT::UnmatchedParen(*ln, codes[*ln].to_string()));
-                        return true;
+                }
+                while let Some(tok) = tok_iter.peek() {
+                    if tok.get_type() != TokType::SPACE {
+                        break;
                     }
+                    tok_iter.next();
                 }
-            }
 
-            // Check for unmatched or empty condition
-            if in_parentheses {
-                
-                errors.push(ErrT::UnmatchedParen(*ln, codes[*ln].to_string()));
-                return true;
-            }
-            if cond.is_empty() {
-                errors.push(ErrT::EmptyCond(*ln, codes[*ln].to_string()));
-                return true;
+                if tok_iter.peek().is_some_and(|t| t.get_value() == "if") {
+                    tok_iter.next();
+                    let cond = match collect_cond(tok_iter, codes, errors, ln) {
+                        Some(cond) => cond,
+                        None => return true,
+                    };
+                    let cond_ln = *ln;
+                    let condition = match parse_expr(&cond, cond_ln, vars, ctx, errors) {
+                        Some(cond) => cond,
+                        None => return true,
+                    };
+                    let (body_tokens, body_ln) = match collect_body(tok_iter, errors, ln, "else if")
+                    {
+                        Some(body) => body,
+                        None => return true,
+                    };
+                    let body = parse_body(
+                        &body_tokens,
+                        codes,
+                        file,
+                        errors,
+                        &vars.nested(ScopeKind::Block),
+                        ctx,
+                        body_ln,
+                    );
+                    elifs.push((cond_ln, condition, body));
+                } else {
+                    let (body_tokens, body_ln) = match collect_body(tok_iter, errors, ln, "else") {
+                        Some(body) => body,
+                        None => return true,
+                    };
+                    else_body = Some(parse_body(
+                        &body_tokens,
+                        codes,
+                        file,
+                        errors,
+                        &vars.nested(ScopeKind::Block),
+                        ctx,
+                        body_ln,
+                    ));
+                    break;
+                }
             }
 
-            // Parse the condition
-            let cond_parsed = parse_condition(&cond, *ln, errors, vars, nst);
-            let condition = match cond_parsed {
-                Some(cond) => cond,
-                None => {
-                    errors.push(ErrT::InVCond(*ln, cond.clone()));
-                    return true;
+            nst.push(NST::NIF(condition, body, elifs, else_body));
+            true
+        }
+        (TokType::CMD, "else") => {
+            // An `else` reaching here was not preceded by an `if` body
+            errors.push(ErrT::DanglingElse(*ln, codeline(codes, *ln)));
+            while let Some(tok) = tok_iter.peek() {
+                if tok.get_type() == TokType::EOL {
+                    break;
                 }
-            };
-
-            // Parse body inside braces with brace counting
-            while let Some(tok) = tok_iter.next() {
-                match (tok.get_type(), tok.get_value()) {
-                    (TokType::OP, "{") => {
-                        brace_count += 1; // Increment brace count
-                        if brace_count == 1 {
-                            continue; // Skip the first `{` to start body parsing
-                        }
-                    }
-                    (TokType::OP, "}") => {
-                        brace_count -= 1; // Decrement brace count
-                        if brace_count == 0 {
-                            break; // Exit body parsing
-                        }
-                    }
-                    (_, _) if brace_count > 0 => {
-                        body_tokens.push(tok.clone());
-                    }
-                    _ => {}
+                if tok.get_type() == TokType::OP && tok.get_value() == "{" {
+                    collect_body(tok_iter, &mut Vec::new(), ln, "else");
+                    break;
                 }
+                tok_iter.next();
             }
-
-            // Check for unmatched braces
-            if brace_count != 0 {
-                errors.push(ErrT::UnmatchedParen(
-                    *ln,
-                    "Unmatched braces in while loop".to_string(),
-                ));
-                return true;
+            true
+        }
+        (TokType::CMD, word @ ("push" | "pop" | "remove")) => {
+            let line = *ln;
+            let mut text = word.to_string();
+            for tok in tok_iter.by_ref() {
+                if tok.get_type() == TokType::EOL {
+                    *ln += 1;
+                    break;
+                }
+                text.push_str(tok.get_value());
             }
-
-            // Check for empty body
-            if body_tokens.is_empty() {
-                errors.push(ErrT::InVCond(*ln, "Empty body for while loop".to_string()));
-                return true;
+            if let Some(e) = parse_expr(text.trim(), line, vars, ctx, errors) {
+                nst.push(NST::Eval(e));
             }
-
-            // Parse the body tokens
-            let body = parse(&body_tokens, codes, file, false, errors);
-            nst.push(NST::NIF(condition, body));
-            return true;
+            true
         }
-        (TokType::CMD, v)
-            if (v != "print"
-                && v != "println"
-                && v != "cls"
-                && v != "wait"
-                && v != "may"
-                && v != "cmd"
-                && v != "while"
-                && v != "if") =>
-        {
+        (TokType::CMD, v) if !KEYWORDS.contains(&v) && assigns(tok_iter) => {
+            let line = *ln;
             let mut isvrd = false;
+            // Anything between the name and `=`, such as the `[i]` of `xs[i] = 5`
+            let mut target = String::new();
             let mut collected_value = String::new();
 
-            while let Some(tok) = tok_iter.next() {
+            for tok in tok_iter.by_ref() {
                 if tok.get_type() == TokType::EOL {
-                    if isvrd {
-                        let var_name = v;
-                        let var_value = collected_value.trim().to_string();
-                        if var_value == "takein()" {
-                            nst.push(NST::VRDInput(var_name.to_string()));
-                            return true;
-                        }
-
-                        if vars.contains_key(var_name) {
-                            let mut vv = if var_value.starts_with('"') && var_value.ends_with('"') {
-                                VVal::Str(var_value.clone())
-                            } else if var_value.parse::<i32>().is_ok() {
-                                VVal::Int(var_value.parse::<i32>().unwrap())
-                            } else if var_value.parse::<f32>().is_ok() {
-                                VVal::F(var_value.parse::<f32>().unwrap())
-                            } else if vars.get(&var_value).is_some() {
-                                VVal::VarRef(var_name.to_string(), var_value.clone())
-                            } else {
-                                errors.push(ErrT::InValidVarVal(*ln, var_value.clone()));
-                                VVal::Str(var_value.clone())
-                            };
+                    *ln += 1;
+                    break;
+                } else if !isvrd && tok.get_type() == TokType::OP && tok.get_value() == "=" {
+                    isvrd = true;
+                } else if isvrd {
+                    collected_value.push_str(tok.get_value());
+                } else {
+                    target.push_str(tok.get_value());
+                }
+            }
 
-                            let mut found = false;
-                            for i in &mut *nst {
-                                if let NST::Input(n) = i {
-                                    if n == var_name {
-                                        vv = VVal::Str(var_value.clone());
-                                        found = true;
-                                        break;
-                                    }
-                                }
+            if isvrd {
+                let var_value = collected_value.trim();
+                if !vars.contains_key(v) {
+                    errors.push(ErrT::VNF(line, v.to_string()));
+                } else if vars.get(v).is_some_and(|b| b.value.is_some()) {
+                    errors.push(ErrT::BadConst(
+                        line,
+                        format!("`{}` is a constant, so it cannot be changed.", v),
+                    ));
+                } else if !target.trim().is_empty() {
+                    let target = format!("{}{}", v, target.trim());
+                    match parse_expr(&target, line, vars, ctx, errors) {
+                        Some(Expr::Index(s, ..)) if s.ty() == ValueType::Str => {
+                            errors.push(ErrT::InvalidOperand(
+                                line,
+                                format!(
+                                    "The string `{}` cannot be changed in place, build a new one with `+`, `slice` or `replace`",
+                                    s
+                                ),
+                            ))
+                        }
+                        Some(Expr::Index(list, idx, elem)) => {
+                            if let Some(value) =
+                                parse_expr_as(var_value, Some(&elem), line, vars, ctx, errors)
+                            {
+                                nst.push(NST::SetIdx(*list, *idx, value));
                             }
-                            if !found {
-                                errors.push(ErrT::InValidVarVal(*ln, var_value.clone()));
-                                return true;
+                        }
+                        Some(Expr::Field(record, name, pos, ty)) => {
+                            if let Some(value) =
+                                parse_expr_as(var_value, Some(&ty), line, vars, ctx, errors)
+                            {
+                                nst.push(NST::SetField(*record, Param { name, ty }, pos, value));
                             }
-
-                            nst.push(NST::VarRD(var_name.to_string(), vv));
-                        } else {
-                            errors.push(ErrT::InValidVarVal(*ln, var_value.clone()));
-                            break;
                         }
+                        Some(_) => errors.push(ErrT::VarRD(line, target)),
+                        None => {}
+                    }
+                } else if let Some(value) = parse_expr_as(
+                    var_value,
+                    vars.get(v).map(|b| &b.ty),
+                    line,
+                    vars,
+                    ctx,
+                    errors,
+                ) {
+                    if value == Expr::Input {
+                        nst.push(NST::VRDInput(v.to_string()));
+                    } else {
+                        nst.push(NST::VarRD(v.to_string(), value));
                     }
-                } else if tok.get_type() == TokType::OP && tok.get_value() == "=" {
-                    isvrd = true;
-                } else if isvrd {
-                    collected_value.push_str(tok.get_value());
                 }
             }
-            *ln += 1;
-            return true;
-        }
-        _ => {
-            return false;
+            true
         }
+        _ => false,
     }
 }
-#[allow(unused)]
-pub fn parse_condition(
-    condition: &str,
-    line_number: usize,
+
+/// Checks whether the rest of the line holds an `=`, which makes it an assignment.
+fn assigns(tok_iter: &std::iter::Peekable<std::slice::Iter<'_, Tokens>>) -> bool {
+    tok_iter
+        .clone()
+        .take_while(|t| t.get_type() != TokType::EOL)
+        .any(|t| t.get_type() == TokType::OP && t.get_value() == "=")
+}
+
+/// Checks whether the next meaningful token (skipping spaces and line breaks) is `else`.
+fn next_is_else(tok_iter: &std::iter::Peekable<std::slice::Iter<'_, Tokens>>) -> bool {
+    // After a one line body the `else` is not the first word of its line, so it isn't a `CMD`
+    tok_iter
+        .clone()
+        .find(|t| !matches!(t.get_type(), TokType::SPACE | TokType::EOL))
+        .is_some_and(|t| {
+            matches!(t.get_type(), TokType::CMD | TokType::INSTR) && t.get_value() == "else"
+        })
+}
+
+/// Collects the text of a parenthesised condition such as `(a == 1)`.
+///
+/// Returns `None` (after recording the error) when the parentheses are unbalanced or empty.
+pub fn collect_cond(
+    tok_iter: &mut std::iter::Peekable<std::slice::Iter<'_, Tokens>>,
+    codes: &[&str],
     errors: &mut Vec<ErrT>,
-    vars: &HashMap<String, VVal>,
-    nst: &Vec<NST>,
-) -> Option<Condition> {
-    let mut index = 0;
-    let mut operand_stack = Vec::new();
-    let mut operator_stack: Vec<String> = Vec::new();
-    let condition = condition.replace(" ", "");
+    ln: &mut usize,
+) -> Option<String> {
+    let mut cond = String::new();
+    let mut depth = 0;
 
-    while index < condition.len() {
-        let c = condition.chars().nth(index).unwrap();
-        match c {
-            '(' => {
-                index += 1;
-                if let Some(nested_condition) =
-                    parse_condition(&condition[index..], line_number, errors, vars, nst)
-                {
-                    operand_stack.push(format!("({})", nested_condition.c_code));
+    for tok in tok_iter.by_ref() {
+        match (tok.get_type(), tok.get_value()) {
+            (TokType::OP, "(") => {
+                if depth > 0 {
+                    cond.push('(');
                 }
+                depth += 1;
             }
-            ')' => {
-                index += 1;
-                break;
+            (TokType::EOL, _) => {
+                *ln += 1;
             }
-            '0'..='9' | '"' | '\'' | 'a'..='z' | 'A'..='Z' | '_' => {
-                if let Some(operand) = parse_operand_char_by_char(
-                    &condition,
-                    &mut index,
-                    line_number,
-                    errors,
-                    vars,
-                    nst,
-                ) {
-                    operand_stack.push(operand);
+            (TokType::SPACE, _) if depth == 0 => {}
+            (TokType::OP, ")") if depth > 0 => {
+                depth -= 1;
+                if depth == 0 {
+                    break; // Exit condition parsing
                 }
+                cond.push(')');
             }
-            '&' | '|' | '=' | '!' | '<' | '>' | '+' | '-' | '*' | '/' => {
-                if let Some(operator) =
-                    parse_operator_char_by_char(&condition, &mut index, line_number, errors)
-                {
-                    while let Some(top_operator) = operator_stack.last() {
-                        if has_higher_precedence(top_operator, &operator) {
-                            apply_operator(
-                                &mut operand_stack,
-                                operator_stack.pop().unwrap(),
-                                line_number,
-                                errors,
-                                vars,
-                            )?;
-                        } else {
-                            break;
-                        }
-                    }
-                    operator_stack.push(operator);
-                }
+            (_, _) if depth > 0 => {
+                cond.push_str(tok.get_value());
             }
             _ => {
-                errors.push(ErrT::InvalidCondOp(
-                    line_number,
-                    format!("Unexpected character: `{}`", c),
-                ));
+                errors.push(ErrT::UnmatchedParen(*ln, codeline(codes, *ln)));
                 return None;
             }
         }
     }
 
-    while let Some(operator) = operator_stack.pop() {
-        apply_operator(&mut operand_stack, operator, line_number, errors, vars)?;
+    // Check for unmatched or empty condition
+    if depth != 0 {
+        errors.push(ErrT::UnmatchedParen(*ln, codeline(codes, *ln)));
+        return None;
     }
-
-    if operand_stack.len() == 1 {
-        let c_code = operand_stack.pop().unwrap();
-        return Some(Condition {
-            left: String::new(),
-            operator: String::new(),
-            right: String::new(),
-            left_type: ValueType::Bool,
-            right_type: ValueType::Bool,
-            c_operator: String::new(),
-            c_code,
-        });
+    if cond.trim().is_empty() {
+        errors.push(ErrT::EmptyCond(*ln, codeline(codes, *ln)));
+        return None;
     }
-
-    errors.push(ErrT::InvalidCondOp(
-        line_number,
-        "Invalid condition structure.".to_string(),
-    ));
-    None
+    Some(cond)
 }
-fn parse_operand_char_by_char(
-    condition: &str,
-    index: &mut usize,
-    line_number: usize,
-    errors: &mut Vec<ErrT>,
-    vars: &HashMap<String, VVal>,
-    nst: &Vec<NST>,
-) -> Option<String> {
-    let mut buffer = String::new();
-    while *index < condition.len() {
-        let c = condition.chars().nth(*index).unwrap();
-        match c {
-            ' ' | '(' | ')' | '&' | '|' | '=' | '!' | '<' | '>' | '+' | '-' | '*' | '/' => break,
-            _ => {
-                buffer.push(c);
-                *index += 1;
-            }
-        }
-    }
 
-    let value_type = determine_value_type(&buffer, vars, errors, line_number, nst)?;
-    match value_type {
-        ValueType::Str | ValueType::Int | ValueType::Float => Some(buffer),
-        _ => {
-            errors.push(ErrT::InVCond(
-                line_number,
-                format!("Invalid operand: {}", buffer),
-            ));
-            None
-        }
+/// Collects the tokens of a `{ ... }` body using brace counting, which must hold at least one
+/// statement.
+///
+/// Returns the body tokens together with the line the body starts on; `ln` is advanced
+/// past every line the body spans.
+pub fn collect_body(
+    tok_iter: &mut std::iter::Peekable<std::slice::Iter<'_, Tokens>>,
+    errors: &mut Vec<ErrT>,
+    ln: &mut usize,
+    what: &str,
+) -> Option<(Vec<Tokens>, usize)> {
+    let (body_tokens, body_ln) = collect_braces(tok_iter, errors, ln, what)?;
+    if body_tokens
+        .iter()
+        .all(|t| matches!(t.get_type(), TokType::SPACE | TokType::EOL))
+    {
+        errors.push(ErrT::InVCond(body_ln, format!("Empty body for {}", what)));
+        return None;
     }
+    Some((body_tokens, body_ln))
 }
 
-fn parse_operator_char_by_char(
-    condition: &str,
-    index: &mut usize,
-    line_number: usize,
+/// Collects the tokens of a `{ ... }` block like `collect_body`, leaving it to the caller to
+/// tell what an empty one is missing.
+pub fn collect_braces(
+    tok_iter: &mut std::iter::Peekable<std::slice::Iter<'_, Tokens>>,
     errors: &mut Vec<ErrT>,
-) -> Option<String> {
-    let mut operator = String::new();
-    while *index < condition.len() {
-        let c = condition.chars().nth(*index).unwrap();
-        match c {
-            '=' | '!' | '<' | '>' | '&' | '|' => {
-                operator.push(c);
-                *index += 1;
+    ln: &mut usize,
+    what: &str,
+) -> Option<(Vec<Tokens>, usize)> {
+    let mut body_tokens = Vec::new();
+    let mut brace_count = 0;
+    let mut body_ln = *ln;
+    // A brace right after a `\\` is printed rather than opening or closing anything
+    let mut escaped = false;
+
+    for tok in tok_iter.by_ref() {
+        let after_escape = escaped;
+        escaped = is_escape(tok, escaped);
+        match (tok.get_type(), tok.get_value()) {
+            (TokType::OP, "{" | "}") if after_escape => {}
+            (TokType::OP, "{") => {
+                brace_count += 1; // Increment brace count
+                if brace_count == 1 {
+                    body_ln = *ln;
+                    continue; // Skip the first `{` to start body parsing
+                }
+            }
+            (TokType::OP, "}") => {
+                brace_count -= 1; // Decrement brace count
+                if brace_count == 0 {
+                    break; // Exit body parsing
+                }
+            }
+            (TokType::EOL, _) => {
+                *ln += 1;
             }
-            _ => break,
+            _ => {}
+        }
+        if brace_count > 0 {
+            body_tokens.push(tok.clone());
         }
     }
 
-    if ["==", "!=", "<", ">", "<=", ">=", "&&", "||"].contains(&operator.as_str()) {
-        Some(operator)
-    } else {
-        errors.push(ErrT::InvalidCondOp(
-            line_number,
-            format!("Invalid operator: `{}`", operator),
-        ));
-        None
-    }
-}
-
-fn has_higher_precedence(op1: &str, op2: &str) -> bool {
-    let precedence = |op: &str| match op {
-        "&&" | "||" => 1,
-        "<" | ">" | "<=" | ">=" => 2,
-        "==" | "!=" => 3,
-        "+" | "-" => 4,
-        "*" | "/" => 5,
-        _ => 0,
-    };
-
-    precedence(op1) > precedence(op2)
-}
+    tidy_body(&mut body_tokens);
 
-fn apply_operator(
-    operand_stack: &mut Vec<String>,
-    operator: String,
-    line_number: usize,
-    errors: &mut Vec<ErrT>,
-    vars: &HashMap<String, VVal>,
-) -> Option<()> {
-    if operand_stack.len() < 2 {
-        errors.push(ErrT::InvalidCondOp(
-            line_number,
-            "Not enough operands for operator.".to_string(),
+    // Check for unmatched braces
+    if brace_count != 0 {
+        errors.push(ErrT::UnmatchedParen(
+            body_ln,
+            format!("Unmatched braces in {} body", what),
         ));
         return None;
     }
 
-    let right = operand_stack.pop().unwrap();
-    let left = operand_stack.pop().unwrap();
-    let combined = if (operator == "==" || operator == "!=")
-        && (
-            (left.starts_with('"') && left.ends_with('"')) // String literal check for left operand
-            || (right.starts_with('"') && right.ends_with('"')) // String literal check for right operand
-            || vars.get(&left).map_or(false, |v| matches!(v, VVal::Str(_))) // Check if left is a variable of type Str
-            || vars.get(&right).map_or(false, |v| matches!(v, VVal::Str(_)))
-            // Check if right is a variable of type Str
-        ) {
-        format!(
-            "strcmp({}, {}) {} 0",
-            left,
-            right,
-            if operator == "==" { "==" } else { "!=" }
-        )
-    } else {
-        format!("{} {} {}", left, operator, right)
-    };
+    Some((body_tokens, body_ln))
+}
 
-    operand_stack.push(combined);
-    Some(())
+/// Whether `tok` is a `\\` that escapes the token after it, given whether it is escaped itself.
+pub fn is_escape(tok: &Tokens, escaped: bool) -> bool {
+    !escaped && tok.get_type() == TokType::OP && tok.get_value() == "\\"
 }
-fn determine_value_type(
-    expr: &str,
-    vars: &HashMap<String, VVal>,
-    errors: &mut Vec<ErrT>,
-    line_number: usize,
-    nst: &Vec<NST>,
-) -> Option<ValueType> {
-    if expr.starts_with('"') || expr.starts_with('\'') {
-        return Some(ValueType::Str);
-    }
 
-    match vars.get(expr) {
-        Some(VVal::Int(_)) => Some(ValueType::Int),
-        Some(VVal::F(_)) => Some(ValueType::Float),
-        Some(VVal::Str(_)) => Some(ValueType::Str),
-        _ => {
-            if expr.parse::<i32>().is_ok() {
-                Some(ValueType::Int)
-            } else if expr.parse::<f32>().is_ok() {
-                Some(ValueType::Float)
-            } else {
-                for i in nst {
-                    if let NST::Input(n) = i {
-                        if n == expr {
-                            return Some(ValueType::Str);
-                        }
-                    }
-                }
-                errors.push(ErrT::VNF(line_number, expr.to_string()));
-                None
-            }
+/// Readies the tokens of a body for `parse_body`.
+///
+/// A statement on the line of the `{` starts with a word the lexer took for an argument, as
+/// only the first word of a line is a command, and the spaces before a `}` on the same line
+/// are not part of the last statement.
+pub fn tidy_body(body: &mut Vec<Tokens>) {
+    if let Some(tok) = body
+        .iter_mut()
+        .take_while(|t| t.get_type() != TokType::EOL)
+        .find(|t| t.get_type() != TokType::SPACE)
+    {
+        if tok.get_type() == TokType::INSTR {
+            *tok = Tokens::mktok(TokType::CMD, tok.get_value().to_string());
         }
     }
+    while body.last().is_some_and(|t| t.get_type() == TokType::SPACE) {
+        body.pop();
+    }
+}
+
+fn codeline(codes: &[&str], ln: usize) -> String {
+    codes
+        .get(ln.saturating_sub(1))
+        .map(|c| c.to_string())
+        .unwrap_or_default()
 }
@@ -1,12 +1,17 @@
 use crate::{
     err::ErrT,
+    expr::{parse_expr, Expr, UnOp},
     lex::{TokType, Tokens},
-    p::{parse, VVal, NST},
-    p2::parse_condition,
+    module::parse_use,
+    p::{
+        global_err, parse_body, parse_params, shadow_err, skip_line, Binding, Ctx, Pat, Stmt, VVal,
+        NST,
+    },
+    p2::{collect_body, collect_braces, collect_cond, ValueType},
+    scope::{ScopeKind, Symbols},
 };
-use std::collections::HashMap;
 
-#[allow(unused)]
+#[allow(clippy::too_many_arguments)]
 pub fn p3(
     tok: &Tokens,
     tokiter: &mut std::iter::Peekable<std::slice::Iter<'_, Tokens>>,
@@ -14,111 +19,750 @@ pub fn p3(
     errors: &mut Vec<ErrT>,
     nst: &mut Vec<NST>,
     ln: &mut usize,
-    vars: &HashMap<String, VVal>,
+    vars: &Symbols<Binding>,
+    ctx: &mut Ctx,
     file: &str,
 ) {
     match (tok.get_type(), tok.get_value()) {
         (TokType::CMD, "while") => {
-            let mut cond = String::new();
-            let mut body_tokens = Vec::new();
-            let mut in_parentheses = false;
-            let mut brace_count = 0;
-
-            // Parse condition inside parentheses
-            while let Some(tok) = tokiter.next() {
-                match (tok.get_type(), tok.get_value()) {
-                    (TokType::OP, "(") if !in_parentheses => {
-                        in_parentheses = true;
-                    }
-                    (TokType::EOL, _) => {
-                        *ln += 1;
-                    }
-                    (TokType::OP, ")") if in_parentheses => {
-                        in_parentheses = false;
-                        break; // Exit condition parsing
-                    }
-                    (TokType::SPACE, _) => {
-                        continue; // Ignore spaces inside condition
-                    }
-                    (_, _) if in_parentheses => {
-                        cond.push_str(tok.get_value());
-                    }
-                    _ => {
-                        println!("unmatched paren\n\n\n");
+            let cond = match collect_cond(tokiter, codes, errors, ln) {
+                Some(cond) => cond,
+                None => return,
+            };
 
-                        errors.push(ErrT::UnmatchedParen(*ln, codes[*ln].to_string()));
-                        return;
-                    }
+            let condition = match parse_expr(&cond, *ln, vars, ctx, errors) {
+                Some(cond) => cond,
+                None => return,
+            };
+
+            let (body_tokens, body_ln) = match collect_body(tokiter, errors, ln, "while loop") {
+                Some(body) => body,
+                None => return,
+            };
+
+            // Parse the body tokens
+            ctx.loops += 1;
+            let body = parse_body(
+                &body_tokens,
+                codes,
+                file,
+                errors,
+                &vars.nested(ScopeKind::Block),
+                ctx,
+                body_ln,
+            );
+            ctx.loops -= 1;
+            nst.push(NST::NWHILE(condition, body));
+        }
+        (TokType::CMD, "for") => {
+            let start = *ln;
+            let mut header = String::new();
+            while let Some(t) = tokiter.next_if(|t| {
+                !(t.get_type() == TokType::EOL
+                    || t.get_type() == TokType::OP && t.get_value() == "{")
+            }) {
+                header.push_str(t.get_value());
+            }
+            // The body opens on the header's line, anything else is an unfinished header
+            if tokiter.peek().is_none_or(|t| t.get_type() == TokType::EOL) {
+                errors.push(ErrT::BadFor(
+                    start,
+                    format!(
+                        "Expected `{{` to open the body after `for{}`.",
+                        header.trim_end()
+                    ),
+                ));
+                return;
+            }
+
+            let (body_tokens, body_ln) = match collect_body(tokiter, errors, ln, "for loop") {
+                Some(body) => body,
+                None => return,
+            };
+
+            // `for x in xs` walks a list, while a header with a range counts
+            if let Some((var, list)) = header
+                .split_once(" in ")
+                .filter(|(_, r)| split_once_top(r, "..").is_none())
+            {
+                let Some((var, list, elem)) =
+                    parse_each_header(var, list, start, errors, vars, ctx)
+                else {
+                    return;
+                };
+                // The loop variable belongs to the body's scope
+                if vars.global(&var) {
+                    errors.push(global_err(start, &var));
+                }
+                let mut body_vars = vars.nested(ScopeKind::Block);
+                body_vars.declare(&var, Binding::var(elem));
+                ctx.loops += 1;
+                let body = parse_body(&body_tokens, codes, file, errors, &body_vars, ctx, body_ln);
+                ctx.loops -= 1;
+                nst.push(NST::NEACH(var, list, body));
+                return;
+            }
+            let Some((var, from, to, inclusive, step)) =
+                parse_for_header(&header, start, errors, vars, ctx)
+            else {
+                return;
+            };
+
+            // C declares the end and step next to the loop variable, so they cannot read a
+            // variable it shadows
+            if [Some(&from), Some(&to), step.as_ref()]
+                .into_iter()
+                .flatten()
+                .any(|e| e.uses(&var))
+            {
+                errors.push(shadow_err(start, &var));
+                return;
+            }
+            if vars.global(&var) {
+                errors.push(global_err(start, &var));
+            }
+            // The loop variable only exists inside the body
+            let mut body_vars = vars.nested(ScopeKind::Block);
+            body_vars.declare(&var, Binding::var(ValueType::Int));
+            ctx.loops += 1;
+            let body = parse_body(&body_tokens, codes, file, errors, &body_vars, ctx, body_ln);
+            ctx.loops -= 1;
+            nst.push(NST::NFOR(var, from, to, inclusive, step, body));
+        }
+        (TokType::CMD, "shape") => {
+            let start = *ln;
+            let mut name = String::new();
+            while let Some(t) = tokiter.peek() {
+                if matches!(t.get_type(), TokType::EOL)
+                    || (t.get_type() == TokType::OP && t.get_value() == "{")
+                {
+                    break;
                 }
+                name.push_str(t.get_value());
+                tokiter.next();
             }
+            let (body_tokens, _) = match collect_braces(tokiter, errors, ln, "shape") {
+                Some(body) => body,
+                None => return,
+            };
+            let name = name.trim().to_string();
 
-            // Check for unmatched or empty condition
-            if in_parentheses {
-                
-                errors.push(ErrT::UnmatchedParen(*ln, codes[*ln].to_string()));
+            let bad = |why: String| ErrT::BadShape(start, why);
+            if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+                errors.push(bad(format!("`{}` is not a valid shape name.", name)));
+                return;
+            }
+            if ValueType::from_name(&name, ctx).is_some() {
+                errors.push(bad(format!("The type `{}` already exists.", name)));
+                return;
+            }
+            // Fields are separated by commas or line breaks
+            let fields: String = body_tokens
+                .iter()
+                .map(|t| match t.get_type() {
+                    TokType::EOL => ",",
+                    _ => t.get_value(),
+                })
+                .collect();
+            let before = errors.len();
+            let fields = parse_params(&fields, "field", start, errors, ctx);
+            if errors.len() > before {
+                return;
+            }
+            if fields.is_empty() {
+                errors.push(bad(format!(
+                    "The shape {} has no fields, a shape needs at least one field.",
+                    name
+                )));
                 return;
             }
-            if cond.is_empty() {
-                errors.push(ErrT::EmptyCond(*ln, codes[*ln].to_string()));
+            if let Some(dup) = fields
+                .iter()
+                .enumerate()
+                .find(|(i, f)| fields[..*i].iter().any(|g| g.name == f.name))
+            {
+                errors.push(bad(format!(
+                    "The field `{}` is declared twice.",
+                    dup.1.name
+                )));
                 return;
             }
+            ctx.shapes.insert(name.clone(), fields.clone());
+            nst.push(NST::Shape(name, fields));
+        }
+        (TokType::CMD, "use") => {
+            let mut text = String::new();
+            for t in tokiter.by_ref() {
+                if t.get_type() == TokType::EOL {
+                    break;
+                }
+                text.push_str(t.get_value());
+            }
+            parse_use(text.trim(), *ln, file, nst, errors, vars, ctx);
+            *ln += 1;
+        }
+        (TokType::CMD, "enum") => {
+            let start = *ln;
+            let mut name = String::new();
+            while let Some(t) = tokiter.peek() {
+                if matches!(t.get_type(), TokType::EOL)
+                    || (t.get_type() == TokType::OP && t.get_value() == "{")
+                {
+                    break;
+                }
+                name.push_str(t.get_value());
+                tokiter.next();
+            }
+            let (body_tokens, _) = match collect_braces(tokiter, errors, ln, "enum") {
+                Some(body) => body,
+                None => return,
+            };
+            let name = name.trim().to_string();
 
-            // Parse the condition
-            let cond_parsed = parse_condition(&cond, *ln, errors, vars, nst);
-            let mut condition = match cond_parsed {
-                Some(cond) => cond,
-                None => {
-                    errors.push(ErrT::InVCond(*ln, cond.clone()));
+            let bad = |why: String| ErrT::BadEnum(start, why);
+            if !is_name(&name) {
+                errors.push(bad(format!("`{}` is not a valid enum name.", name)));
+                return;
+            }
+            if ValueType::from_name(&name, ctx).is_some() {
+                errors.push(bad(format!("The type `{}` already exists.", name)));
+                return;
+            }
+            // Variants are separated by commas or line breaks
+            let text: String = body_tokens
+                .iter()
+                .map(|t| match t.get_type() {
+                    TokType::EOL => ",",
+                    _ => t.get_value(),
+                })
+                .collect();
+            let mut variants: Vec<String> = Vec::new();
+            for v in text.split(',').map(str::trim).filter(|v| !v.is_empty()) {
+                if !is_name(v) {
+                    errors.push(bad(format!("`{}` is not a valid variant name.", v)));
+                    return;
+                }
+                if variants.iter().any(|w| w == v) {
+                    errors.push(bad(format!("The variant `{}` is declared twice.", v)));
                     return;
                 }
+                variants.push(v.to_string());
+            }
+            if variants.is_empty() {
+                errors.push(bad(format!(
+                    "The enum {} has no variants, an enum needs at least one variant.",
+                    name
+                )));
+                return;
+            }
+            ctx.enums.insert(name.clone(), variants.clone());
+            nst.push(NST::Enum(name, variants));
+        }
+        (TokType::CMD, "match") => {
+            let start = *ln;
+            let mut header = String::new();
+            while let Some(t) = tokiter.next_if(|t| {
+                !(t.get_type() == TokType::EOL
+                    || t.get_type() == TokType::OP && t.get_value() == "{")
+            }) {
+                header.push_str(t.get_value());
+            }
+            if tokiter.peek().is_none_or(|t| t.get_type() == TokType::EOL) {
+                errors.push(ErrT::BadMatch(
+                    start,
+                    format!(
+                        "Expected `{{` to open the arms after `match{}`.",
+                        header.trim_end()
+                    ),
+                ));
+                return;
+            }
+            let (body_tokens, body_ln) = match collect_body(tokiter, errors, ln, "match") {
+                Some(body) => body,
+                None => return,
+            };
+            let Some(value) = parse_expr(header.trim(), start, vars, ctx, errors) else {
+                return;
             };
-            // Parse body inside braces with brace counting
-            while let Some(tok) = tokiter.next() {
-                match (tok.get_type(), tok.get_value()) {
+            if !matches!(
+                value.ty(),
+                ValueType::Int | ValueType::Str | ValueType::Enum(_)
+            ) {
+                errors.push(ErrT::BadMatch(
+                    start,
+                    format!(
+                        "`{}` is of type {}, but only ints, strings and enums can be matched.",
+                        value,
+                        value.ty()
+                    ),
+                ));
+                return;
+            }
+            if let Some(m) = parse_match(
+                value,
+                &body_tokens,
+                body_ln,
+                start,
+                codes,
+                file,
+                errors,
+                vars,
+                ctx,
+            ) {
+                nst.push(m);
+            }
+        }
+        (TokType::CMD, word @ ("break" | "continue")) => {
+            if ctx.loops == 0 {
+                errors.push(ErrT::NotInLoop(*ln, word.to_string()));
+            } else if word == "break" {
+                nst.push(NST::Break);
+            } else {
+                nst.push(NST::Continue);
+            }
+            skip_line(tokiter, ln);
+        }
+        // A first word that is no keyword and assigns nothing, and words or symbols left over
+        // by the statement before them, would otherwise be dropped without a word
+        (TokType::CMD | TokType::INSTR | TokType::OP, _) => {
+            let mut code = tok.get_value().to_string();
+            while let Some(t) = tokiter.next_if(|t| t.get_type() != TokType::EOL) {
+                code.push_str(t.get_value());
+            }
+            errors.push(ErrT::Unexpected(*ln, code.trim().to_string()));
+        }
+        _ => {}
+    }
+}
+
+/// Whether `s` can name a shape, enum or variant.
+fn is_name(s: &str) -> bool {
+    !s.is_empty() && s.chars().all(|c| c.is_alphanumeric() || c == '_')
+}
+
+/// Splits the body of a `match` into its arms and type checks their patterns.
+#[allow(clippy::too_many_arguments)]
+fn parse_match(
+    value: Expr,
+    toks: &[Tokens],
+    body_ln: usize,
+    start: usize,
+    codes: &[&str],
+    file: &str,
+    errors: &mut Vec<ErrT>,
+    vars: &Symbols<Binding>,
+    ctx: &mut Ctx,
+) -> Option<NST> {
+    let ty = value.ty();
+    let mut arms: Vec<(Vec<Pat>, Vec<Stmt>)> = Vec::new();
+    let mut default: Option<Vec<Stmt>> = None;
+    let mut line = body_ln;
+    let mut i = 0;
+    let mut ok = true;
+
+    while i < toks.len() {
+        match toks[i].get_type() {
+            TokType::SPACE => {
+                i += 1;
+                continue;
+            }
+            TokType::EOL => {
+                line += 1;
+                i += 1;
+                continue;
+            }
+            _ => {}
+        }
+
+        // Everything up to `=>` is the pattern
+        let arm_ln = line;
+        let mut pats = String::new();
+        while i < toks.len()
+            && toks[i].get_type() != TokType::EOL
+            && !(toks[i].get_value() == "="
+                && toks.get(i + 1).is_some_and(|t| t.get_value() == ">"))
+        {
+            pats.push_str(toks[i].get_value());
+            i += 1;
+        }
+        if toks.get(i).is_none_or(|t| t.get_type() == TokType::EOL) {
+            errors.push(ErrT::BadMatch(
+                arm_ln,
+                format!("Expected `=>` after the pattern `{}`.", pats.trim()),
+            ));
+            ok = false;
+            continue;
+        }
+        i += 2;
+        while toks.get(i).is_some_and(|t| t.get_type() == TokType::SPACE) {
+            i += 1;
+        }
+
+        // The arm is either a `{ ... }` block or the rest of the line
+        let mut body = Vec::new();
+        if toks.get(i).is_some_and(|t| t.get_value() == "{") {
+            let mut depth = 0;
+            while let Some(t) = toks.get(i) {
+                i += 1;
+                match (t.get_type(), t.get_value()) {
                     (TokType::OP, "{") => {
-                        println!("brace count : {}",brace_count);
-                        brace_count += 1; // Increment brace count
-                        if brace_count == 1 {
-                            continue; // Skip the first `{` to start body parsing
+                        depth += 1;
+                        if depth == 1 {
+                            continue;
                         }
                     }
                     (TokType::OP, "}") => {
-                        println!("brace count : {}",brace_count);
-                        brace_count -= 1; // Decrement brace count
-                        if brace_count == 0 {
-                            break; // Exit body parsing
+                        depth -= 1;
+                        if depth == 0 {
+                            break;
+                        }
+                    }
+                    (TokType::EOL, _) => line += 1,
+                    _ => {}
+                }
+                body.push(t.clone());
+            }
+        } else {
+            while let Some(t) = toks.get(i) {
+                if t.get_type() == TokType::EOL {
+                    break;
+                }
+                body.push(t.clone());
+                i += 1;
+            }
+        }
+        body.push(Tokens::mktok(TokType::EOL, "\n".to_string()));
+        let body = parse_body(
+            &as_lines(&body),
+            codes,
+            file,
+            errors,
+            &vars.nested(ScopeKind::Block),
+            ctx,
+            arm_ln,
+        );
+
+        let pats = pats.trim();
+        if default.is_some() {
+            errors.push(ErrT::BadMatch(
+                arm_ln,
+                "The `_` arm must be the last one.".to_string(),
+            ));
+            ok = false;
+        } else if pats == "_" {
+            default = Some(body);
+        } else {
+            match parse_pats(pats, &ty, &arms, arm_ln, errors, vars, ctx) {
+                Some(pats) => arms.push((pats, body)),
+                None => ok = false,
+            }
+        }
+    }
+
+    if let (ValueType::Enum(name), None) = (&ty, &default) {
+        let missing: Vec<String> = ctx.enums[name]
+            .iter()
+            .enumerate()
+            .filter(|(idx, _)| {
+                !arms
+                    .iter()
+                    .flat_map(|(p, _)| p)
+                    .any(|p| matches!(p, Pat::Val(Expr::Variant(_, _, i)) if i == idx))
+            })
+            .map(|(_, v)| format!("`{}`", v))
+            .collect();
+        if ok && !missing.is_empty() {
+            errors.push(ErrT::BadMatch(
+                start,
+                format!(
+                    "The match on `{}` does not cover {}, add arms for them or a `_` arm.",
+                    value,
+                    missing.join(", ")
+                ),
+            ));
+            ok = false;
+        }
+    }
+    ok.then_some(NST::Match(value, arms, default))
+}
+
+/// Marks the first word of every line as a statement, since an arm's body may start
+/// after `=>` in the middle of a line.
+fn as_lines(toks: &[Tokens]) -> Vec<Tokens> {
+    let mut out = Vec::with_capacity(toks.len());
+    let mut first = true;
+    for t in toks {
+        match t.get_type() {
+            TokType::EOL => first = true,
+            TokType::SPACE => {}
+            TokType::INSTR if first => {
+                out.push(Tokens::mktok(TokType::CMD, t.get_value().to_string()));
+                first = false;
+                continue;
+            }
+            _ => first = false,
+        }
+        out.push(t.clone());
+    }
+    out
+}
+
+/// Parses the comma separated patterns of one arm, such as `1, 5..=9` or `Red, Green`, where
+/// a constant stands for its value.
+fn parse_pats(
+    text: &str,
+    ty: &ValueType,
+    arms: &[(Vec<Pat>, Vec<Stmt>)],
+    ln: usize,
+    errors: &mut Vec<ErrT>,
+    vars: &Symbols<Binding>,
+    ctx: &Ctx,
+) -> Option<Vec<Pat>> {
+    let bad = |errors: &mut Vec<ErrT>, why: String| {
+        errors.push(ErrT::BadMatch(ln, why));
+        None
+    };
+    let konst = |s: &str| match vars.get(s.trim()) {
+        Some(Binding {
+            value: Some(value), ..
+        }) => Some(value.clone()),
+        _ => None,
+    };
+    let mut pats: Vec<Pat> = Vec::new();
+    for alt in split_top(text) {
+        let alt = alt.trim();
+        let pat = match ty {
+            ValueType::Enum(name) => match konst(alt) {
+                Some(v) if v.ty() == *ty => Pat::Val(v),
+                _ => {
+                    let variant = alt
+                        .strip_prefix(name.as_str())
+                        .and_then(|v| v.strip_prefix('.'))
+                        .unwrap_or(alt);
+                    match ctx.enums[name].iter().position(|v| v == variant) {
+                        Some(idx) => {
+                            Pat::Val(Expr::Variant(name.clone(), variant.to_string(), idx))
+                        }
+                        None => {
+                            errors.push(ErrT::BadEnum(
+                                ln,
+                                format!("The enum {} has no variant `{}`", name, variant),
+                            ));
+                            return None;
                         }
                     }
-                    (_, _) if brace_count > 0 => {
-                        body_tokens.push(tok.clone());
+                }
+            },
+            ValueType::Int => {
+                let int = |s: &str| match konst(s) {
+                    Some(Expr::Lit(VVal::Int(i))) => Some(i),
+                    _ => s.replace(' ', "").parse::<i32>().ok(),
+                };
+                match alt.split_once("..") {
+                    Some((from, to)) => {
+                        let (to, inclusive) = match to.strip_prefix('=') {
+                            Some(to) => (to, true),
+                            None => (to, false),
+                        };
+                        let (Some(from), Some(to)) = (int(from), int(to)) else {
+                            return bad(
+                                errors,
+                                format!("`{}` is not a range of int literals or constants.", alt),
+                            );
+                        };
+                        let to = if inclusive {
+                            Some(to)
+                        } else {
+                            to.checked_sub(1)
+                        };
+                        match to {
+                            Some(to) if from <= to => Pat::Range(from, to),
+                            _ => return bad(errors, format!("The range `{}` is empty.", alt)),
+                        }
                     }
+                    None => match int(alt) {
+                        Some(i) => Pat::Val(Expr::Lit(VVal::Int(i))),
+                        None => {
+                            return bad(
+                                errors,
+                                format!("`{}` is not an int literal, a constant or a range.", alt),
+                            )
+                        }
+                    },
+                }
+            }
+            _ => {
+                let lit = alt
+                    .strip_prefix('"')
+                    .and_then(|s| s.strip_suffix('"'))
+                    .filter(|s| !s.contains('"'));
+                match (lit, konst(alt)) {
+                    (Some(s), _) => Pat::Val(Expr::Lit(VVal::Str(s.to_string()))),
+                    (None, Some(v @ Expr::Lit(VVal::Str(_)))) => Pat::Val(v),
                     _ => {
+                        return bad(
+                            errors,
+                            format!("`{}` is not a string literal or constant.", alt),
+                        )
                     }
                 }
             }
+        };
 
-            // Check for unmatched braces
-            if brace_count != 0 {
-                println!("unmatched paren");
+        // A value that an earlier pattern already matches can never reach this one
+        let covered = arms
+            .iter()
+            .flat_map(|(p, _)| p)
+            .chain(&pats)
+            .any(|p| match (p, &pat) {
+                (Pat::Val(a), Pat::Val(b)) => a == b,
+                (Pat::Range(lo, hi), Pat::Val(Expr::Lit(VVal::Int(i)))) => lo <= i && i <= hi,
+                _ => false,
+            });
+        if covered {
+            return bad(
+                errors,
+                format!("`{}` is already covered by an earlier arm.", alt),
+            );
+        }
+        pats.push(pat);
+    }
+    Some(pats)
+}
 
-                println!("braces : {}",brace_count);
-                errors.push(ErrT::UnmatchedParen(
-                    *ln,
-                    "Unmatched braces in while loop".to_string(),
-                ));
-                return;
+/// Splits on commas that are not inside a string literal.
+fn split_top(text: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut in_str = false;
+    let mut from = 0;
+    for (i, c) in text.char_indices() {
+        match c {
+            '"' => in_str = !in_str,
+            ',' if !in_str => {
+                parts.push(&text[from..i]);
+                from = i + 1;
             }
+            _ => {}
+        }
+    }
+    parts.push(&text[from..]);
+    parts
+}
 
-            // Check for empty body
-            if body_tokens.is_empty() {
-                errors.push(ErrT::InVCond(*ln, "Empty body for while loop".to_string()));
-                return;
+/// Splits around the first `pat` that is not inside a string literal or brackets, such as the
+/// `..` of a range rather than one in `split("a..b", ".")`.
+fn split_once_top<'a>(text: &'a str, pat: &str) -> Option<(&'a str, &'a str)> {
+    let mut quote = None;
+    let mut depth = 0usize;
+    for (i, c) in text.char_indices() {
+        match (c, quote) {
+            (c, Some(q)) if c == q => quote = None,
+            (_, Some(_)) => {}
+            ('"' | '\'', None) => quote = Some(c),
+            ('(' | '[' | '{', None) => depth += 1,
+            (')' | ']' | '}', None) => depth = depth.saturating_sub(1),
+            _ if depth == 0 && text[i..].starts_with(pat) => {
+                return Some((&text[..i], &text[i + pat.len()..]));
             }
+            _ => {}
+        }
+    }
+    None
+}
 
-            // Parse the body tokens
-            let body = parse(&body_tokens, codes, file, false, errors);
-            nst.push(NST::NWHILE(condition, body));
+/// Parses `i in 0..10 step 2` (the step is optional and `..=` includes the end).
+fn parse_for_header(
+    header: &str,
+    ln: usize,
+    errors: &mut Vec<ErrT>,
+    vars: &Symbols<Binding>,
+    ctx: &Ctx,
+) -> Option<(String, Expr, Expr, bool, Option<Expr>)> {
+    let bad = |errors: &mut Vec<ErrT>, why: &str| {
+        errors.push(ErrT::BadFor(ln, why.to_string()));
+        None
+    };
+
+    let Some((var, range)) = header.split_once(" in ") else {
+        return bad(errors, "Expected `for <name> in <start>..<end>`.");
+    };
+    let var = var.trim();
+    if var.is_empty() || !var.chars().all(|c| c.is_alphanumeric() || c == '_') {
+        return bad(errors, "The loop variable must be a plain name.");
+    }
+    let (range, step) = match split_once_top(range, " step ") {
+        Some((range, step)) => (range, Some(step)),
+        None => (range, None),
+    };
+    let Some((from, to)) = split_once_top(range, "..") else {
+        return bad(errors, "Expected a range such as `0..10` or `1..=10`.");
+    };
+    let (to, inclusive) = match to.strip_prefix('=') {
+        Some(to) => (to, true),
+        None => (to, false),
+    };
+
+    let from = parse_expr(from, ln, vars, ctx, errors)?;
+    let to = parse_expr(to, ln, vars, ctx, errors)?;
+    let step = match step {
+        Some(step) => Some(parse_expr(step, ln, vars, ctx, errors)?),
+        None => None,
+    };
+    if [Some(&from), Some(&to), step.as_ref()]
+        .into_iter()
+        .flatten()
+        .any(|e| e.ty() != ValueType::Int)
+    {
+        return bad(errors, "The range bounds and the step must be of type int.");
+    }
+    if step_sign(step.as_ref()) == Some(0) {
+        return bad(errors, "The step of a `for` loop cannot be 0.");
+    }
+    Some((var.to_string(), from, to, inclusive, step))
+}
+
+/// Parses the two halves of `x in xs`, giving the loop variable, the list or map and the type
+/// the loop variable takes.
+fn parse_each_header(
+    var: &str,
+    list: &str,
+    ln: usize,
+    errors: &mut Vec<ErrT>,
+    vars: &Symbols<Binding>,
+    ctx: &Ctx,
+) -> Option<(String, Expr, ValueType)> {
+    let var = var.trim();
+    if var.is_empty() || !var.chars().all(|c| c.is_alphanumeric() || c == '_') {
+        errors.push(ErrT::BadFor(
+            ln,
+            "The loop variable must be a plain name.".to_string(),
+        ));
+        return None;
+    }
+    let list = parse_expr(list, ln, vars, ctx, errors)?;
+    // Lists give their elements and maps their keys
+    let elem = match list.ty() {
+        ValueType::List(elem) | ValueType::Map(elem, _) => *elem,
+        ty => {
+            errors.push(ErrT::BadFor(
+                ln,
+                format!(
+                    "Expected a range such as `0..10`, a list or a map, but `{}` is of type {}.",
+                    list, ty
+                ),
+            ));
+            return None;
         }
-        _ => {}
+    };
+    Some((var.to_string(), list, elem))
+}
+
+/// The sign of a loop step when it is a literal, so the direction is known while compiling.
+pub fn step_sign(step: Option<&Expr>) -> Option<i32> {
+    match step {
+        None => Some(1),
+        Some(Expr::Lit(VVal::Int(i))) => Some(i.signum()),
+        Some(Expr::Un(UnOp::Neg, e, _)) => step_sign(Some(e)).map(|s| -s),
+        Some(_) => None,
     }
 }
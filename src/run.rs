@@ -1,101 +1,721 @@
 use clearscreen::clear;
 use colored::Colorize;
 use std::{
+    cell::RefCell,
     collections::HashMap,
     io::{stdin, stdout, Write},
+    path::Path,
     process::exit,
+    rc::Rc,
     thread::sleep,
     time::Duration,
 };
 
-use crate::p::{VVal, NST};
+use crate::{
+    build::{grammar_flag, read_file},
+    expr::{BinOp, Builtin, Expr, UnOp},
+    lex::{lex, Tokens},
+    p::{parse, NMap, Param, Pat, PrintPart, Stmt, VVal, NST},
+    p2::ValueType,
+    scope::{ScopeKind, Symbols},
+};
+
+/// Entry point for `neit run <file>`: lexes, parses and interprets the file.
+pub fn run_file(args: &[String]) {
+    if args.len() < 3 {
+        eprintln!("{}", "Error :~ No source file given to run".red());
+        exit(-1);
+    }
+    let src = Path::new(&args[2]);
+    if !src.is_file() {
+        eprintln!(
+            "{} {}",
+            "Error :~ Cannot stat source file :".red(),
+            src.display()
+        );
+        exit(-1);
+    }
+
+    let grammar = grammar_flag(&args[2..]);
+    let code = read_file(src, grammar.as_deref());
+    let mut toks = Tokens::new();
+    lex(&code, &mut toks);
+    let nst = parse(
+        &toks,
+        &code.split('\n').collect::<Vec<&str>>(),
+        src.display().to_string().as_str(),
+        grammar.as_deref(),
+        true,
+        &mut Vec::new(),
+    );
+
+    if args.contains(&"-cls".to_string()) {
+        if let Err(e) = clear() {
+            eprintln!("{}", format!("Failed to clear screen: {}", e).red());
+        }
+    }
+    run(&nst);
+}
 
-#[allow(unused)]
-pub fn run(nst: &[NST]) {
-    let mut vars: HashMap<String, VVal> = HashMap::new();
+/// A command's parameters, return type and body.
+type Func<'a> = (&'a [Param], &'a Option<ValueType>, &'a [Stmt]);
 
-    for t in nst {
-        if let NST::Var(v) = t {
-            vars.insert(v.name.clone(), v.value.clone());
+/// Runtime state of the interpreter: the variables in scope and the defined commands.
+struct Env<'a> {
+    vars: Symbols<VVal>,
+    funcs: HashMap<String, Func<'a>>,
+}
+
+impl<'a> Env<'a> {
+    fn get(&self, name: &str) -> Option<&VVal> {
+        self.vars.get(name)
+    }
+
+    fn declare(&mut self, name: &str, value: VVal) {
+        self.vars.declare(name, value);
+    }
+
+    fn assign(&mut self, name: &str, value: VVal) {
+        if !self.vars.set(name, value.clone()) {
+            self.declare(name, value);
         }
     }
+}
 
-    for t in nst {
-        if let NST::PRINT(txt) = t {
-            let mut inv = false;
-            let mut vname = String::new();
-            let mut output = String::new();
+pub fn run(nst: &[Stmt]) {
+    let mut env = Env {
+        vars: Symbols::new(),
+        funcs: HashMap::new(),
+    };
+
+    // Commands are hoisted like the C functions they compile to
+    for (_, t) in nst {
+        if let NST::Func(name, args, ret, body) = t {
+            env.funcs.insert(name.clone(), (args, ret, body));
+        }
+    }
+
+    exec(nst, &mut env);
+}
+
+/// How control leaves a block.
+enum Flow {
+    Next,
+    Give(VVal),
+    Break,
+    Continue,
+}
+
+fn exec<'a>(nst: &'a [Stmt], env: &mut Env<'a>) -> Flow {
+    for (_, t) in nst {
+        match t {
+            NST::PRINT(parts) => print_parts(parts, env),
+            NST::Var(v) => {
+                let value = coerce(eval(&v.value, env), &v.ty);
+                env.declare(&v.name, value);
+            }
+            NST::Input(v) => {
+                let input = read_input();
+                env.declare(v, VVal::Str(input));
+            }
+            NST::VRDInput(v) => {
+                let input = read_input();
+                env.assign(v, VVal::Str(input));
+            }
+            NST::VarRD(n, v) => {
+                let value = eval(v, env);
+                let value = match (env.get(n), value) {
+                    // A float variable stays a float when given an int, as in C
+                    (Some(VVal::F(_)), VVal::Int(i)) => VVal::F(i as f32),
+                    (_, value) => value,
+                };
+                env.assign(n, value);
+            }
+            // Hoisted by `run`, as commands can only be declared at the top level
+            NST::Func(..) => {}
+            NST::NCLRSCRN => match clear() {
+                Ok(_) => {}
+                Err(e) => {
+                    eprintln!("{}", format!("Failed to clear screen: {}", e).red());
+                    exit(1);
+                }
+            },
+            NST::WAIT(t) => sleep(Duration::from_millis(*t)),
+            NST::NIF(cond, body, elifs, else_body) => {
+                let flow = if truthy(&eval(cond, env)) {
+                    exec_block(body, env)
+                } else if let Some((_, _, body)) =
+                    elifs.iter().find(|(_, cond, _)| truthy(&eval(cond, env)))
+                {
+                    exec_block(body, env)
+                } else if let Some(body) = else_body {
+                    exec_block(body, env)
+                } else {
+                    Flow::Next
+                };
+                if !matches!(flow, Flow::Next) {
+                    return flow;
+                }
+            }
+            NST::NWHILE(cond, body) => {
+                while truthy(&eval(cond, env)) {
+                    match exec_block(body, env) {
+                        Flow::Give(v) => return Flow::Give(v),
+                        Flow::Break => break,
+                        Flow::Next | Flow::Continue => {}
+                    }
+                }
+            }
+            NST::NFOR(var, from, to, inclusive, step, body) => {
+                let from = int(eval(from, env));
+                let to = int(eval(to, env));
+                let step = step.as_ref().map_or(1, |s| int(eval(s, env)));
+                if step == 0 {
+                    runtime_error("The step of a `for` loop cannot be 0");
+                }
 
-            let mut chars = txt.chars().peekable();
-            while let Some(c) = chars.next() {
-                if c == '\\' {
-                    if let Some(next_char) = chars.next() {
-                        let escaped = match next_char {
-                            '\\' => '\\',
-                            'n' => '\n',
-                            't' => '\t',
-                            '"' => '"',
-                            _ => next_char,
-                        };
-                        output.push(escaped);
+                // The loop variable lives in its own scope around the body, like a C `for`
+                env.vars.push(ScopeKind::Block);
+                env.declare(var, VVal::Int(from));
+                let mut flow = Flow::Next;
+                loop {
+                    let i = env.get(var).map_or(0, |v| int(v.clone()));
+                    let go = match (step > 0, inclusive) {
+                        (true, true) => i <= to,
+                        (true, false) => i < to,
+                        (false, true) => i >= to,
+                        (false, false) => i > to,
+                    };
+                    if !go {
+                        break;
                     }
-                } else if c == '{' {
-                    inv = true;
-                } else if inv {
-                    if c == '}' {
-                        if let Some(value) = vars.get(&vname) {
-                            output.push_str(
-                                &(match value {
-                                    VVal::Str(s) => s.clone(),
-                                    VVal::Int(i) => i.to_string(),
-                                    VVal::F(f) => f.to_string(),
-                                    VVal::VarRef(ref_name, _) => resolve_variable(ref_name, &vars),
-                                })
-                                .to_string(),
-                            );
+                    match exec_block(body, env) {
+                        Flow::Give(v) => {
+                            flow = Flow::Give(v);
+                            break;
                         }
-                        vname.clear();
-                        inv = false;
-                    } else {
-                        vname.push(c);
+                        Flow::Break => break,
+                        // `continue` still steps the variable, as in C
+                        Flow::Next | Flow::Continue => {}
                     }
-                } else {
-                    output.push(c);
+                    // A step past the ends of int can only overshoot the range, so it ends the loop
+                    let i = env.get(var).map_or(0, |v| int(v.clone()));
+                    match i.checked_add(step) {
+                        Some(next) => env.assign(var, VVal::Int(next)),
+                        None => break,
+                    }
+                }
+                env.vars.pop();
+                if let Flow::Give(_) = flow {
+                    return flow;
+                }
+            }
+            NST::NEACH(var, list, body) => {
+                let list = eval(list, env);
+                // The length is read on every step, so the body sees elements it pushes
+                let mut i = 0;
+                while let Some(item) = nth(&list, i) {
+                    env.vars.push(ScopeKind::Block);
+                    env.declare(var, item);
+                    let flow = exec(body, env);
+                    env.vars.pop();
+                    match flow {
+                        Flow::Give(v) => return Flow::Give(v),
+                        Flow::Break => break,
+                        Flow::Next | Flow::Continue => {}
+                    }
+                    i += 1;
+                }
+            }
+            NST::SetIdx(target, idx, value) => match (target.ty(), eval(target, env)) {
+                // The value may be a command that changes the list, so the index is only
+                // checked once it is worked out
+                (ValueType::List(elem), VVal::List(items)) => {
+                    let i = int(eval(idx, env));
+                    let value = coerce(eval(value, env), &elem);
+                    let i = index(&items, i);
+                    items.borrow_mut()[i] = value;
+                }
+                (ValueType::Map(_, val), VVal::Map(map)) => {
+                    let key = eval(idx, env);
+                    let value = coerce(eval(value, env), &val);
+                    map.borrow_mut().set(key, value);
                 }
+                _ => unreachable!("index assignment on a value that is not a list or a map"),
+            },
+            NST::Eval(e) => {
+                eval(e, env);
+            }
+            // Fields are read by position, so the declaration itself has nothing to run
+            NST::Shape(..) | NST::Enum(..) => {}
+            NST::Match(value, arms, default) => {
+                let value = eval(value, env);
+                let body = arms
+                    .iter()
+                    .find(|(pats, _)| {
+                        pats.iter().any(|p| match (p, &value) {
+                            (Pat::Range(lo, hi), VVal::Int(i)) => lo <= i && i <= hi,
+                            (Pat::Val(e), _) => compare(BinOp::Eq, &value, &eval(e, env)),
+                            _ => false,
+                        })
+                    })
+                    .map(|(_, body)| body)
+                    .or(default.as_ref());
+                if let Some(body) = body {
+                    let flow = exec_block(body, env);
+                    if !matches!(flow, Flow::Next) {
+                        return flow;
+                    }
+                }
+            }
+            NST::SetField(record, field, pos, value) => {
+                let VVal::Shape(fields) = eval(record, env) else {
+                    unreachable!("field assignment on a value that is not a shape")
+                };
+                let value = coerce(eval(value, env), &field.ty);
+                fields.borrow_mut()[*pos] = value;
             }
+            NST::Call(name, args) => {
+                call(name, args, env);
+            }
+            NST::Give(v) => return Flow::Give(eval(v, env)),
+            NST::Break => return Flow::Break,
+            NST::Continue => return Flow::Continue,
+        }
+    }
+    Flow::Next
+}
 
-            print!("{}", output);
-            if let Err(e) = stdout().flush() {
-                eprintln!("{}", format!("STDOUT failed: {}", e).red());
-                exit(1);
+/// Executes a nested body in its own scope, mirroring a C block.
+fn exec_block<'a>(nst: &'a [Stmt], env: &mut Env<'a>) -> Flow {
+    env.vars.push(ScopeKind::Block);
+    let flow = exec(nst, env);
+    env.vars.pop();
+    flow
+}
+
+/// Runs a command and returns the value it gives, if any.
+fn call<'a>(name: &str, args: &[Expr], env: &mut Env<'a>) -> Option<VVal> {
+    let &(params, ret, body) = env.funcs.get(name)?;
+    let values: Vec<VVal> = params
+        .iter()
+        .zip(args)
+        .map(|(p, a)| coerce(eval(a, env), &p.ty))
+        .collect();
+    // The command's scope hides the caller's variables, like the C function it becomes
+    env.vars.push(ScopeKind::Cmd);
+    for (p, v) in params.iter().zip(values) {
+        env.declare(&p.name, v);
+    }
+    let flow = exec(body, env);
+    env.vars.pop();
+
+    match (flow, ret) {
+        (Flow::Give(v), Some(ty)) => Some(coerce(v, ty)),
+        _ => None,
+    }
+}
+
+/// Evaluates an expression, running any commands it calls.
+fn eval<'a>(e: &Expr, env: &mut Env<'a>) -> VVal {
+    match e {
+        Expr::Lit(v) => v.clone(),
+        Expr::Var(name, ty) => env.get(name).cloned().unwrap_or_else(|| ty.zero()),
+        // `&&` and `||` short-circuit, so a call on the right may never run
+        Expr::Bin(l, BinOp::And, r, _) => {
+            VVal::Bool(truthy(&eval(l, env)) && truthy(&eval(r, env)))
+        }
+        Expr::Bin(l, BinOp::Or, r, _) => VVal::Bool(truthy(&eval(l, env)) || truthy(&eval(r, env))),
+        Expr::Bin(l, op, r, _) if op.is_comparison() => {
+            let l = eval(l, env);
+            let r = eval(r, env);
+            VVal::Bool(compare(*op, &l, &r))
+        }
+        Expr::Bin(l, op, r, _) => {
+            let l = eval(l, env);
+            let r = eval(r, env);
+            arith(*op, l, r)
+        }
+        Expr::Un(UnOp::Neg, e, _) => match eval(e, env) {
+            VVal::Int(i) => VVal::Int(i.wrapping_neg()),
+            VVal::F(f) => VVal::F(-f),
+            v => v,
+        },
+        Expr::Un(UnOp::Not, e, _) => VVal::Bool(!truthy(&eval(e, env))),
+        Expr::Call(name, args, ty) => call(name, args, env).unwrap_or_else(|| ty.zero()),
+        Expr::Input => VVal::Str(read_input()),
+        Expr::List(items, ty) => {
+            let ValueType::List(elem) = ty else {
+                unreachable!("a list literal always has a list type")
+            };
+            let items = items.iter().map(|e| coerce(eval(e, env), elem)).collect();
+            VVal::List(Rc::new(RefCell::new(items)))
+        }
+        Expr::Fill(value, n, ty) => {
+            let ValueType::List(elem) = ty else {
+                unreachable!("a list literal always has a list type")
+            };
+            let value = coerce(eval(value, env), elem);
+            VVal::List(Rc::new(RefCell::new(vec![value; *n])))
+        }
+        Expr::Map(pairs, ty) => {
+            let ValueType::Map(_, val) = ty else {
+                unreachable!("a map literal always has a map type")
+            };
+            let mut map = NMap::default();
+            for (k, v) in pairs {
+                let key = eval(k, env);
+                let value = coerce(eval(v, env), val);
+                map.set(key, value);
             }
-        } else if let NST::Input(v) = t {
-            let mut input = String::new();
-            stdin().read_line(&mut input).unwrap();
-            vars.insert(v.to_string(), VVal::Str(input.trim().to_string()));
-        } else if let NST::NCLRSCRN = t {
-            match clear() {
-                Ok(_) => {}
-                Err(e) => {
-                    eprintln!("{}", format!("Failed to clear screen: {}", e).red());
-                    exit(1);
+            VVal::Map(Rc::new(RefCell::new(map)))
+        }
+        Expr::Shape(_, fields) => {
+            let values = fields
+                .iter()
+                .map(|(f, v)| coerce(eval(v, env), &f.ty))
+                .collect();
+            VVal::Shape(Rc::new(RefCell::new(values)))
+        }
+        Expr::Variant(_, variant, idx) => VVal::Enum(*idx, variant.clone()),
+        Expr::Field(record, _, pos, _) => {
+            let VVal::Shape(fields) = eval(record, env) else {
+                unreachable!("reading a field of a value that is not a shape")
+            };
+            let value = fields.borrow()[*pos].clone();
+            value
+        }
+        Expr::Index(target, idx, _) => match eval(target, env) {
+            VVal::List(items) => {
+                let i = index(&items, int(eval(idx, env)));
+                let value = items.borrow()[i].clone();
+                value
+            }
+            VVal::Map(map) => {
+                let key = eval(idx, env);
+                let value = map.borrow().get(&key).cloned();
+                value.unwrap_or_else(|| {
+                    runtime_error(&format!("The key {} is not in the map", show(&key)))
+                })
+            }
+            VVal::Str(s) => {
+                let i = int(eval(idx, env));
+                if i < 0 || i as usize >= s.len() {
+                    runtime_error(&format!(
+                        "Index {} is out of range for a string of length {}",
+                        i,
+                        s.len()
+                    ));
+                }
+                if !s.is_char_boundary(i as usize) {
+                    runtime_error(&format!("Index {} is in the middle of a character", i));
+                }
+                let c = s[i as usize..].chars().next().unwrap();
+                VVal::Str(c.to_string())
+            }
+            _ => unreachable!("indexing a value that is not a list, a map or a string"),
+        },
+        Expr::Builtin(b, args, ty) => {
+            let items = match (b, eval(&args[0], env)) {
+                (Builtin::ToInt | Builtin::ToFloat | Builtin::ToStr, value) => {
+                    return convert(*b, value);
+                }
+                (_, VVal::Str(s)) => {
+                    let rest: Vec<VVal> = args[1..].iter().map(|a| eval(a, env)).collect();
+                    return str_builtin(*b, &s, &rest);
+                }
+                (_, VVal::List(items)) => items,
+                (_, VVal::Map(map)) => {
+                    let key = args.get(1).map(|k| eval(k, env));
+                    return match (b, key) {
+                        (Builtin::Len, _) => VVal::Int(map.borrow().entries.len() as i32),
+                        (Builtin::Has, Some(key)) => VVal::Bool(map.borrow().get(&key).is_some()),
+                        (Builtin::Remove, Some(key)) => {
+                            map.borrow_mut().remove(&key);
+                            VVal::Map(map)
+                        }
+                        _ => unreachable!("`{}` on a map", b.name()),
+                    };
+                }
+                _ => unreachable!("`{}` on a value that is not a list or a map", b.name()),
+            };
+            match b {
+                Builtin::Len => VVal::Int(items.borrow().len() as i32),
+                Builtin::Pop => items
+                    .borrow_mut()
+                    .pop()
+                    .unwrap_or_else(|| runtime_error("Cannot pop from an empty list")),
+                Builtin::Push => {
+                    let ValueType::List(elem) = ty else {
+                        unreachable!("push always gives back a list")
+                    };
+                    let value = coerce(eval(&args[1], env), elem);
+                    items.borrow_mut().push(value);
+                    VVal::List(items)
+                }
+                _ => unreachable!("`{}` on a list", b.name()),
+            }
+        }
+    }
+}
+
+/// Applies a string builtin byte by byte, the way the `__NSTR_*__` functions in `nulibc` do.
+/// Lengths and positions count bytes, but nothing cuts a character in two.
+fn str_builtin(b: Builtin, s: &str, args: &[VVal]) -> VVal {
+    let arg = |i: usize| match &args[i] {
+        VVal::Str(a) => a.as_str(),
+        _ => unreachable!("`{}` with an argument that is not a string", b.name()),
+    };
+    let space = |c: char| matches!(c, ' ' | '\t' | '\n' | '\r' | '\x0b' | '\x0c');
+    match b {
+        Builtin::Len => VVal::Int(s.len() as i32),
+        Builtin::Upper => VVal::Str(s.to_ascii_uppercase()),
+        Builtin::Lower => VVal::Str(s.to_ascii_lowercase()),
+        Builtin::Trim => VVal::Str(s.trim_matches(space).to_string()),
+        Builtin::Contains => VVal::Bool(s.contains(arg(0))),
+        Builtin::StartsWith => VVal::Bool(s.starts_with(arg(0))),
+        Builtin::EndsWith => VVal::Bool(s.ends_with(arg(0))),
+        // An empty pattern would match between every byte, so it replaces nothing
+        Builtin::Replace if arg(0).is_empty() => VVal::Str(s.to_string()),
+        Builtin::Replace => VVal::Str(s.replace(arg(0), arg(1))),
+        Builtin::Split => {
+            let parts: Vec<VVal> = if arg(0).is_empty() {
+                s.chars().map(|c| VVal::Str(c.to_string())).collect()
+            } else {
+                s.split(arg(0)).map(|p| VVal::Str(p.to_string())).collect()
+            };
+            VVal::List(Rc::new(RefCell::new(parts)))
+        }
+        Builtin::Slice => {
+            let (from, to) = (int(args[0].clone()), int(args[1].clone()));
+            if from < 0 || to < from || to as usize > s.len() {
+                runtime_error(&format!(
+                    "The slice {}..{} is out of range for a string of length {}",
+                    from,
+                    to,
+                    s.len()
+                ));
+            }
+            if !s.is_char_boundary(from as usize) || !s.is_char_boundary(to as usize) {
+                runtime_error(&format!(
+                    "The slice {}..{} cuts a character in two",
+                    from, to
+                ));
+            }
+            VVal::Str(s[from as usize..to as usize].to_string())
+        }
+        _ => unreachable!("`{}` on a string", b.name()),
+    }
+}
+
+/// Applies `int`, `float` or `str`, failing like `__NTO_INT__` and `__NTO_FLOAT__` when a
+/// string doesn't parse, and like `__NFLOAT_INT__` when a float is out of range.
+fn convert(b: Builtin, value: VVal) -> VVal {
+    match (b, value) {
+        (Builtin::ToStr, value) => VVal::Str(fmt_val(&value)),
+        (Builtin::ToInt, VVal::Str(s)) => match s.trim().parse::<i32>() {
+            Ok(i) => VVal::Int(i),
+            Err(_) => runtime_error(&format!("Cannot convert \"{}\" to an int", s)),
+        },
+        (_, VVal::Str(s)) => match s.trim().parse::<f32>() {
+            Ok(f) => VVal::F(f),
+            Err(_) => runtime_error(&format!("Cannot convert \"{}\" to a float", s)),
+        },
+        // C leaves a float that doesn't fit in an `int` undefined, so `__NFLOAT_INT__` stops there
+        (Builtin::ToInt, VVal::F(f))
+            if f.is_nan() || !(-2147483648.0..2147483648.0).contains(&f) =>
+        {
+            runtime_error(&format!(
+                "Cannot convert {} to an int",
+                fmt_val(&VVal::F(f))
+            ))
+        }
+        (Builtin::ToInt, value) => VVal::Int(int(value)),
+        (_, value) => VVal::F(num(&value) as f32),
+    }
+}
+
+/// The `i`th element of a list or key of a map, for `for ... in`.
+fn nth(list: &VVal, i: usize) -> Option<VVal> {
+    match list {
+        VVal::List(items) => items.borrow().get(i).cloned(),
+        VVal::Map(map) => map.borrow().entries.get(i).map(|(k, _)| k.clone()),
+        _ => unreachable!("`for ... in` over a value that is not a list or a map"),
+    }
+}
+
+/// Checks an index against a list's bounds, failing the same way `__NLIST_AT__` does.
+fn index(items: &RefCell<Vec<VVal>>, i: i32) -> usize {
+    let len = items.borrow().len();
+    if i < 0 || i as usize >= len {
+        runtime_error(&format!(
+            "Index {} is out of range for a list of length {}",
+            i, len
+        ));
+    }
+    i as usize
+}
+
+/// Applies an arithmetic operator the way the generated C does: integer operations stay
+/// integers (wrapping, truncating division) and anything involving a float is a float.
+fn arith(op: BinOp, l: VVal, r: VVal) -> VVal {
+    match (l, r) {
+        (VVal::Str(a), VVal::Str(b)) => VVal::Str(a + &b),
+        (VVal::Int(a), VVal::Int(b)) => VVal::Int(match op {
+            BinOp::Add => a.wrapping_add(b),
+            BinOp::Sub => a.wrapping_sub(b),
+            BinOp::Mul => a.wrapping_mul(b),
+            BinOp::Div => {
+                if b == 0 {
+                    runtime_error("Division by zero");
+                }
+                a.wrapping_div(b)
+            }
+            BinOp::Mod => {
+                if b == 0 {
+                    runtime_error("Modulo by zero");
                 }
+                a.wrapping_rem(b)
             }
-        } else if let NST::WAIT(t) = t {
-            sleep(Duration::from_millis(*t));
+            _ => unreachable!("`{}` is not arithmetic", op.symbol()),
+        }),
+        (l, r) => {
+            let (a, b) = (num(&l) as f32, num(&r) as f32);
+            VVal::F(match op {
+                BinOp::Add => a + b,
+                BinOp::Sub => a - b,
+                BinOp::Mul => a * b,
+                BinOp::Div => a / b,
+                _ => unreachable!("`{}` is not arithmetic", op.symbol()),
+            })
         }
     }
 }
 
-fn resolve_variable(name: &str, vars: &HashMap<String, VVal>) -> String {
-    if let Some(value) = vars.get(name) {
-        match value {
-            VVal::Str(s) => s.clone(),
-            VVal::Int(i) => i.to_string(),
-            VVal::F(f) => f.to_string(),
-            VVal::VarRef(ref_name, _) => resolve_variable(ref_name, vars),
+/// Applies a comparison; strings compare bytewise like `strcmp`.
+fn compare(op: BinOp, l: &VVal, r: &VVal) -> bool {
+    let ord = match (l, r) {
+        (VVal::Str(a), VVal::Str(b)) => a.as_bytes().cmp(b.as_bytes()),
+        (VVal::Int(a), VVal::Int(b)) => a.cmp(b),
+        (VVal::Enum(a, _), VVal::Enum(b, _)) => a.cmp(b),
+        _ => match num(l).partial_cmp(&num(r)) {
+            Some(ord) => ord,
+            // NaN compares unequal to everything
+            None => return op == BinOp::Ne,
+        },
+    };
+    match op {
+        BinOp::Eq => ord.is_eq(),
+        BinOp::Ne => ord.is_ne(),
+        BinOp::Lt => ord.is_lt(),
+        BinOp::Gt => ord.is_gt(),
+        BinOp::Le => ord.is_le(),
+        _ => ord.is_ge(),
+    }
+}
+
+fn runtime_error(msg: &str) -> ! {
+    eprintln!("{}", format!("Runtime error :~ {}", msg).red());
+    exit(1);
+}
+
+/// Converts an argument to the declared parameter type, as the C call would.
+fn coerce(v: VVal, ty: &ValueType) -> VVal {
+    match (v, ty) {
+        (VVal::Int(i), ValueType::Float) => VVal::F(i as f32),
+        (VVal::F(f), ValueType::Int) => VVal::Int(f as i32),
+        (v, _) => v,
+    }
+}
+
+fn read_input() -> String {
+    let mut input = String::new();
+    if let Err(e) = stdin().read_line(&mut input) {
+        eprintln!("{}", format!("STDIN failed: {}", e).red());
+        exit(1);
+    }
+    input.trim_end_matches(['\n', '\r']).to_string()
+}
+
+fn print_parts<'a>(parts: &[PrintPart], env: &mut Env<'a>) {
+    let mut output = String::new();
+    for part in parts {
+        match part {
+            PrintPart::Text(t) => output.push_str(t),
+            PrintPart::Expr(e) => output.push_str(&fmt_val(&eval(e, env))),
         }
-    } else {
-        format!("Unknown variable: {}", name)
+    }
+
+    print!("{}", output);
+    if let Err(e) = stdout().flush() {
+        eprintln!("{}", format!("STDOUT failed: {}", e).red());
+        exit(1);
+    }
+}
+
+/// Formats a value the same way the generated `printf` call does.
+fn fmt_val(value: &VVal) -> String {
+    match value {
+        VVal::Str(s) => s.clone(),
+        VVal::Int(i) => i.to_string(),
+        // `%f` writes NaN in lower case and keeps its sign
+        VVal::F(f) if f.is_nan() && f.is_sign_negative() => "-nan".to_string(),
+        VVal::F(f) if f.is_nan() => "nan".to_string(),
+        VVal::F(f) => format!("{:.6}", f),
+        VVal::Bool(b) => b.to_string(),
+        VVal::List(items) => {
+            let items: Vec<String> = items.borrow().iter().map(show).collect();
+            format!("[{}]", items.join(", "))
+        }
+        VVal::Map(map) => {
+            let entries: Vec<String> = map
+                .borrow()
+                .entries
+                .iter()
+                .map(|(k, v)| format!("{}: {}", show(k), show(v)))
+                .collect();
+            format!("{{{}}}", entries.join(", "))
+        }
+        VVal::Enum(_, variant) => variant.clone(),
+        VVal::Shape(_) => unreachable!("shapes are rejected in print statements"),
+    }
+}
+
+/// Formats a value inside a list or a map, where strings are quoted.
+fn show(value: &VVal) -> String {
+    match value {
+        VVal::Str(s) => format!("\"{}\"", s),
+        v => fmt_val(v),
+    }
+}
+
+/// The integer inside a value that the parser has already checked to be an `int`.
+fn int(v: VVal) -> i32 {
+    match v {
+        VVal::Int(i) => i,
+        VVal::F(f) => f as i32,
+        VVal::Bool(b) => b as i32,
+        VVal::Enum(i, _) => i as i32,
+        VVal::Str(_) | VVal::List(_) | VVal::Map(_) | VVal::Shape(_) => 0,
+    }
+}
+
+fn truthy(v: &VVal) -> bool {
+    match v {
+        VVal::Int(i) => *i != 0,
+        VVal::F(f) => *f != 0.0,
+        VVal::Bool(b) => *b,
+        VVal::Str(_) | VVal::List(_) | VVal::Map(_) | VVal::Shape(_) | VVal::Enum(..) => true,
+    }
+}
+
+fn num(v: &VVal) -> f64 {
+    match v {
+        VVal::Int(i) => *i as f64,
+        VVal::F(f) => *f as f64,
+        VVal::Bool(b) => *b as i32 as f64,
+        VVal::Str(s) => s.parse().unwrap_or(0.0),
+        VVal::Enum(i, _) => *i as f64,
+        VVal::List(_) | VVal::Map(_) | VVal::Shape(_) => 0.0,
     }
 }
@@ -0,0 +1,118 @@
+use std::collections::HashMap;
+
+/// What opened a scope, which decides how far a lookup from inside it reaches.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScopeKind {
+    /// The top level of a file.
+    Global,
+    /// The body of a command, which also holds its parameters.
+    Cmd,
+    /// The body of an `if`, `while`, `for` or `match` arm.
+    Block,
+}
+
+/// The variables visible at one point of a program, one scope per enclosing body with the
+/// innermost last.
+///
+/// The parser and code generation keep the type of each variable and the interpreter its
+/// value. Lookups walk outwards to the body of the enclosing command and from there jump to
+/// the top level, whose variables become C globals; the blocks of the caller stay hidden.
+#[derive(Debug, Clone)]
+pub struct Symbols<T> {
+    scopes: Vec<(ScopeKind, HashMap<String, T>)>,
+}
+
+impl<T> Default for Symbols<T> {
+    fn default() -> Self {
+        Symbols {
+            scopes: vec![(ScopeKind::Global, HashMap::new())],
+        }
+    }
+}
+
+impl<T> Symbols<T> {
+    pub fn new() -> Self {
+        Symbols::default()
+    }
+
+    pub fn push(&mut self, kind: ScopeKind) {
+        self.scopes.push((kind, HashMap::new()));
+    }
+
+    pub fn pop(&mut self) {
+        self.scopes.pop();
+    }
+
+    /// A copy with a new, empty scope inside the current one.
+    pub fn nested(&self, kind: ScopeKind) -> Self
+    where
+        T: Clone,
+    {
+        let mut inner = self.clone();
+        inner.push(kind);
+        inner
+    }
+
+    /// Where lookups leave the local scopes: the body of the innermost command, or the top level.
+    fn reach(&self) -> usize {
+        self.scopes
+            .iter()
+            .rposition(|(kind, _)| *kind == ScopeKind::Cmd)
+            .unwrap_or(0)
+    }
+
+    /// The position of the scope `name` resolves to, searching the local scopes innermost
+    /// first and then the top level.
+    fn find(&self, name: &str) -> Option<usize> {
+        (self.reach()..self.scopes.len())
+            .rev()
+            .chain([0])
+            .find(|&i| self.scopes[i].1.contains_key(name))
+    }
+
+    pub fn get(&self, name: &str) -> Option<&T> {
+        self.scopes[self.find(name)?].1.get(name)
+    }
+
+    pub fn contains_key(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    /// Whether `name` is declared in the innermost scope itself, where declaring it again
+    /// would clash rather than shadow.
+    pub fn declared_here(&self, name: &str) -> bool {
+        self.scopes
+            .last()
+            .is_some_and(|(_, names)| names.contains_key(name))
+    }
+
+    /// Whether this is the top level of a file rather than the inside of a body.
+    pub fn at_top(&self) -> bool {
+        self.scopes.len() == 1
+    }
+
+    /// Whether `name` is a top level variable seen from inside a command.
+    pub fn global(&self, name: &str) -> bool {
+        self.reach() > 0 && self.find(name) == Some(0)
+    }
+
+    pub fn declare(&mut self, name: &str, value: T) {
+        if let Some((_, names)) = self.scopes.last_mut() {
+            names.insert(name.to_string(), value);
+        }
+    }
+
+    /// Changes the visible variable called `name`, returning `false` if there is none.
+    pub fn set(&mut self, name: &str, value: T) -> bool {
+        match self
+            .find(name)
+            .and_then(|at| self.scopes[at].1.get_mut(name))
+        {
+            Some(slot) => {
+                *slot = value;
+                true
+            }
+            None => false,
+        }
+    }
+}
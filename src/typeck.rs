@@ -0,0 +1,274 @@
+use crate::{
+    err::ErrT,
+    expr::Expr,
+    p::{Ctx, PrintPart, Stmt, NST},
+    p2::ValueType,
+    scope::{ScopeKind, Symbols},
+};
+
+/// Type checks the statements of one file once it has been parsed, with `ctx` holding the
+/// commands, shapes and enums it declares or imports.
+///
+/// The commands of the modules it uses are part of the tree too, but were checked along with
+/// their own file, so only the file's own commands are looked at.
+pub fn check(nst: &[Stmt], ctx: &Ctx, errors: &mut Vec<ErrT>) {
+    check_block(nst, &mut Symbols::new(), None, ctx, errors);
+}
+
+/// Checks a body in order, declaring its variables in `vars` as they come. `ret` is the
+/// return type of the command the body is in, if any.
+fn check_block(
+    nst: &[Stmt],
+    vars: &mut Symbols<ValueType>,
+    ret: Option<&ValueType>,
+    ctx: &Ctx,
+    errors: &mut Vec<ErrT>,
+) {
+    for (ln, stmt) in nst {
+        check_stmt(stmt, *ln, vars, ret, ctx, errors);
+    }
+}
+
+/// Checks a nested body, which sees the variables of the one around it.
+fn check_nested(
+    nst: &[Stmt],
+    vars: &Symbols<ValueType>,
+    ret: Option<&ValueType>,
+    ctx: &Ctx,
+    errors: &mut Vec<ErrT>,
+) {
+    check_block(nst, &mut vars.nested(ScopeKind::Block), ret, ctx, errors);
+}
+
+/// Checks one statement starting on line `ln`, along with the bodies it holds.
+fn check_stmt(
+    stmt: &NST,
+    ln: usize,
+    vars: &mut Symbols<ValueType>,
+    ret: Option<&ValueType>,
+    ctx: &Ctx,
+    errors: &mut Vec<ErrT>,
+) {
+    match stmt {
+        NST::Var(v) => {
+            check_assign(&v.name, &v.ty, &v.value, ln, errors);
+            vars.declare(&v.name, v.ty.clone());
+        }
+        NST::Input(v) => vars.declare(v, ValueType::Str),
+        NST::VarRD(name, value) => {
+            if let Some(ty) = vars.get(name) {
+                check_assign(name, ty, value, ln, errors);
+            }
+        }
+        NST::VRDInput(name) => {
+            if let Some(ty) = vars.get(name) {
+                check_assign(name, ty, &Expr::Input, ln, errors);
+            }
+        }
+        NST::Func(link, params, ret, body) if owns(link, ctx) => {
+            // Like the C function it becomes, a command only sees its parameters and globals
+            let mut locals = vars.nested(ScopeKind::Cmd);
+            for p in params {
+                locals.declare(&p.name, p.ty.clone());
+            }
+            check_block(body, &mut locals, ret.as_ref(), ctx, errors);
+        }
+        NST::NIF(cond, body, elifs, else_body) => {
+            check_cond(cond, ln, ctx, errors);
+            check_nested(body, vars, ret, ctx, errors);
+            for (cond_ln, cond, body) in elifs {
+                check_cond(cond, *cond_ln, ctx, errors);
+                check_nested(body, vars, ret, ctx, errors);
+            }
+            if let Some(body) = else_body {
+                check_nested(body, vars, ret, ctx, errors);
+            }
+        }
+        NST::NWHILE(cond, body) => {
+            check_cond(cond, ln, ctx, errors);
+            check_nested(body, vars, ret, ctx, errors);
+        }
+        NST::NFOR(var, .., body) => {
+            let mut inner = vars.nested(ScopeKind::Block);
+            inner.declare(var, ValueType::Int);
+            check_block(body, &mut inner, ret, ctx, errors);
+        }
+        NST::NEACH(var, items, body) => {
+            // Lists give their elements and maps their keys
+            let elem = match items.ty() {
+                ValueType::List(elem) | ValueType::Map(elem, _) => *elem,
+                _ => unreachable!("`for ... in` over a value that is not a list or a map"),
+            };
+            let mut inner = vars.nested(ScopeKind::Block);
+            inner.declare(var, elem);
+            check_block(body, &mut inner, ret, ctx, errors);
+        }
+        NST::Match(_, arms, default) => {
+            for (_, body) in arms {
+                check_nested(body, vars, ret, ctx, errors);
+            }
+            if let Some(body) = default {
+                check_nested(body, vars, ret, ctx, errors);
+            }
+        }
+        NST::Give(value) => {
+            // A `give` outside a command or in one without a return type is reported while parsing
+            if let Some(ty) = ret.filter(|ty| !ty.accepts(&value.ty())) {
+                errors.push(ErrT::BadGive(
+                    ln,
+                    format!(
+                        "The value `{}` is of type {}, but the command's return type is {}.",
+                        value,
+                        value.ty(),
+                        ty
+                    ),
+                ));
+            }
+        }
+        NST::SetIdx(target, _, value) => {
+            let (what, elem) = match target.ty() {
+                ValueType::List(elem) => (format!("A list of {}", elem), *elem),
+                ValueType::Map(_, val) => (format!("A map of {} values", val), *val),
+                _ => unreachable!("index assignment on a value that is not a list or a map"),
+            };
+            if !elem.accepts(&value.ty()) {
+                errors.push(ErrT::TypeMismatch(
+                    ln,
+                    format!("{} cannot hold `{}` of type {}", what, value, value.ty()),
+                ));
+            }
+        }
+        NST::SetField(_, field, _, value) if !field.ty.accepts(&value.ty()) => {
+            errors.push(ErrT::TypeMismatch(
+                ln,
+                format!(
+                    "The field `{}` is of type {}, but `{}` is of type {}",
+                    field.name,
+                    field.ty,
+                    value,
+                    value.ty()
+                ),
+            ));
+        }
+        NST::PRINT(parts) => {
+            for part in parts {
+                if let PrintPart::Expr(e) = part {
+                    check_print(e, ln, errors);
+                }
+            }
+        }
+        NST::Call(link, args) => check_args(link, args, ln, ctx, errors),
+        _ => {}
+    }
+
+    for e in exprs(stmt) {
+        check_calls(e, ln, ctx, errors);
+    }
+}
+
+/// Whether a command belongs to the file being checked rather than to a module it uses, whose
+/// commands are linked as `module.name`.
+fn owns(link: &str, ctx: &Ctx) -> bool {
+    link.split_once('.').map(|(ns, _)| ns) == ctx.module.as_deref()
+}
+
+/// Checks that a value fits the declared type of what it is assigned to.
+pub fn check_assign(name: &str, ty: &ValueType, value: &Expr, ln: usize, errors: &mut Vec<ErrT>) {
+    if !ty.accepts(&value.ty()) {
+        errors.push(ErrT::TypeMismatch(
+            ln,
+            format!(
+                "`{}` is of type {} but `{}` is of type {}",
+                name,
+                ty,
+                value,
+                value.ty()
+            ),
+        ));
+    }
+}
+
+/// Checks the condition of an `if`, `else if` or `while`.
+fn check_cond(cond: &Expr, ln: usize, ctx: &Ctx, errors: &mut Vec<ErrT>) {
+    check_calls(cond, ln, ctx, errors);
+    if cond.ty() != ValueType::Bool {
+        errors.push(ErrT::TypeMismatch(
+            ln,
+            format!(
+                "The condition `{}` is of type {}, but a condition must be a bool",
+                cond,
+                cond.ty()
+            ),
+        ));
+    }
+}
+
+/// Checks that an interpolated `{expression}` has a printed form.
+fn check_print(e: &Expr, ln: usize, errors: &mut Vec<ErrT>) {
+    // Shapes have no printed form of their own, only their fields do
+    if !e.ty().printable() {
+        errors.push(ErrT::Unprintable(
+            ln,
+            format!(
+                "`{}` of type {} cannot be printed, print its fields or elements instead",
+                e,
+                e.ty()
+            ),
+        ));
+    }
+}
+
+/// Checks the arguments of every command call inside an expression against the parameters.
+fn check_calls(e: &Expr, ln: usize, ctx: &Ctx, errors: &mut Vec<ErrT>) {
+    if let Expr::Call(link, args, _) = e {
+        check_args(link, args, ln, ctx, errors);
+    }
+    for sub in e.children() {
+        check_calls(sub, ln, ctx, errors);
+    }
+}
+
+fn check_args(link: &str, args: &[Expr], ln: usize, ctx: &Ctx, errors: &mut Vec<ErrT>) {
+    let Some((name, sig)) = ctx.cmds.iter().find(|(_, sig)| sig.link == link) else {
+        return;
+    };
+    for (p, arg) in sig.params.iter().zip(args) {
+        if !p.ty.accepts(&arg.ty()) {
+            errors.push(ErrT::ArgType(
+                ln,
+                format!(
+                    "The parameter `{}` of `{}` is of type {}, but `{}` is of type {}",
+                    p.name,
+                    name,
+                    p.ty,
+                    arg,
+                    arg.ty()
+                ),
+            ));
+        }
+    }
+}
+
+/// The expressions a statement holds itself, leaving out conditions and nested bodies.
+fn exprs(stmt: &NST) -> Vec<&Expr> {
+    match stmt {
+        NST::Var(v) => vec![&v.value],
+        NST::VarRD(_, e) | NST::Eval(e) | NST::Give(e) | NST::NEACH(_, e, _) => vec![e],
+        NST::NFOR(_, from, to, _, step, _) => [Some(from), Some(to), step.as_ref()]
+            .into_iter()
+            .flatten()
+            .collect(),
+        NST::SetIdx(target, idx, value) => vec![target, idx, value],
+        NST::SetField(record, _, _, value) => vec![record, value],
+        NST::Match(value, _, _) => vec![value],
+        NST::PRINT(parts) => parts
+            .iter()
+            .filter_map(|p| match p {
+                PrintPart::Expr(e) => Some(e),
+                PrintPart::Text(_) => None,
+            })
+            .collect(),
+        NST::Call(_, args) => args.iter().collect(),
+        _ => Vec::new(),
+    }
+}
@@ -0,0 +1,142 @@
+//! Builds small projects with `neit build <dir>` and runs the executables they put in
+//! `<dir>/target`.
+//!
+//! The tests need clang and fail without it, unless `NEIT_SKIP_CLANG` is set.
+
+use std::{env, fs, path::Path, process::Command};
+
+const NEIT: &str = env!("CARGO_BIN_EXE_neit");
+
+const MANIFEST: &str = r#"# A project whose entry file and module sit in a subdirectory
+[project]
+name = "greeter"
+entry = "src/app.nsc"
+output = "hello"
+opt = 2
+c_sources = ["native/extra.c"]
+"#;
+
+const APP: &str = "use words.nsc
+may who = call words.name
+println hello {who} {call words.twice {21}}
+";
+
+const WORDS: &str = "cmd name: str {
+  give \"project\"
+}
+cmd twice (n: int): int {
+  give n * 2
+}
+";
+
+/// Whether clang can be started. Without it the test fails, as a skip would pass for a check
+/// that never ran, unless `NEIT_SKIP_CLANG` is set to skip it on purpose.
+fn have_clang() -> bool {
+    if Command::new("clang").arg("--version").output().is_ok() {
+        return true;
+    }
+    assert!(
+        env::var_os("NEIT_SKIP_CLANG").is_some(),
+        "clang is not installed, so nothing can be built. Install it, or set NEIT_SKIP_CLANG=1 to skip the tests that need it"
+    );
+    eprintln!("clang is not installed and NEIT_SKIP_CLANG is set, skipping");
+    false
+}
+
+fn write(path: &Path, text: &str) {
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, text).unwrap();
+}
+
+#[test]
+fn build_a_project_directory() {
+    if !have_clang() {
+        return;
+    }
+
+    let work = env::temp_dir().join(format!("neit-build-project-{}", std::process::id()));
+    let project = work.join("greeter");
+    write(&project.join("neit.toml"), MANIFEST);
+    write(&project.join("src/app.nsc"), APP);
+    write(&project.join("src/words.nsc"), WORDS);
+    write(
+        &project.join("native/extra.c"),
+        "int greeter_extra(void) { return 1; }\n",
+    );
+
+    // Started from elsewhere, so everything has to be found through the project directory
+    let built = Command::new(NEIT)
+        .arg("build")
+        .arg(&project)
+        .current_dir(&work)
+        .output()
+        .unwrap();
+    assert!(
+        built.status.success(),
+        "Building the project failed :~\n{}{}",
+        String::from_utf8_lossy(&built.stdout),
+        String::from_utf8_lossy(&built.stderr)
+    );
+
+    let binary = project
+        .join("target/hello")
+        .with_extension(env::consts::EXE_EXTENSION);
+    let ran = Command::new(&binary).output().unwrap_or_else(|e| {
+        panic!("Unable to start {} :~ {}", binary.display(), e);
+    });
+    let _ = fs::remove_dir_all(&work);
+
+    assert!(ran.status.success());
+    assert_eq!(String::from_utf8_lossy(&ran.stdout), "hello project 42\n");
+}
+
+#[test]
+fn build_a_project_with_a_grammar() {
+    if !have_clang() {
+        return;
+    }
+
+    let work = env::temp_dir().join(format!("neit-build-grammar-{}", std::process::id()));
+    let project = work.join("sayer");
+    write(
+        &project.join("neit.toml"),
+        "[project]\nname = \"sayer\"\nentry = \"main.nsc\"\noutput = \"sayer\"\ngrammar = \"g.txt\"\n",
+    );
+    write(&project.join("g.txt"), "println ~ say\n");
+    write(
+        &project.join("main.nsc"),
+        "use words.nsc\nsay from main\ncall words.speak\n",
+    );
+    // The module is written in the project's grammar too
+    write(
+        &project.join("words.nsc"),
+        "cmd speak {\n  say from module\n}\n",
+    );
+
+    let built = Command::new(NEIT)
+        .arg("build")
+        .arg(&project)
+        .current_dir(&work)
+        .output()
+        .unwrap();
+    assert!(
+        built.status.success(),
+        "Building the project failed :~\n{}{}",
+        String::from_utf8_lossy(&built.stdout),
+        String::from_utf8_lossy(&built.stderr)
+    );
+
+    let binary = project
+        .join("target/sayer")
+        .with_extension(env::consts::EXE_EXTENSION);
+    let ran = Command::new(&binary).output().unwrap_or_else(|e| {
+        panic!("Unable to start {} :~ {}", binary.display(), e);
+    });
+    let _ = fs::remove_dir_all(&work);
+
+    assert!(ran.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&ran.stdout),
+        "from main\nfrom module\n"
+    );
+}
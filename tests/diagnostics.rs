@@ -0,0 +1,311 @@
+//! Feeds programs that should not compile to `neit run` and checks that each one is rejected with
+//! the expected error, before anything runs.
+
+use std::{env, fs, process::Command};
+
+const NEIT: &str = env!("CARGO_BIN_EXE_neit");
+
+/// Writes `src` to a file named after the test, checks it with `neit run` and gives back what it
+/// reported. Panics when the program was accepted.
+fn rejects(name: &str, src: &str) -> String {
+    rejects_files(name, &[(&format!("{}.nsc", name), src)])
+}
+
+/// Like `rejects`, for a program spread over several files that are written side by side,
+/// starting from the first one.
+fn rejects_files(name: &str, files: &[(&str, &str)]) -> String {
+    let dir = env::temp_dir().join(format!("neit-diagnostics-{}-{}", std::process::id(), name));
+    fs::create_dir_all(&dir).unwrap();
+    for (file, src) in files {
+        fs::write(dir.join(file), src).unwrap();
+    }
+    let out = Command::new(NEIT)
+        .arg("run")
+        .arg(dir.join(files[0].0))
+        .current_dir(&dir)
+        .output()
+        .unwrap();
+    let _ = fs::remove_dir_all(&dir);
+
+    let report = format!(
+        "{}{}",
+        String::from_utf8_lossy(&out.stdout),
+        String::from_utf8_lossy(&out.stderr)
+    );
+    assert!(
+        !out.status.success(),
+        "`{}` should have been rejected :~\n{}",
+        name,
+        report
+    );
+    report
+}
+
+/// Checks that `report` mentions every one of `expected`.
+fn assert_reports(report: &str, expected: &[&str]) {
+    for text in expected {
+        assert!(
+            report.contains(text),
+            "Expected the report to mention `{}` :~\n{}",
+            text,
+            report
+        );
+    }
+}
+
+#[test]
+fn give_on_every_path() {
+    let report = rejects(
+        "give_in_loop",
+        "cmd loopy (n:int): int {\n  while (n > 0) {\n    give n\n  }\n}\n",
+    );
+    assert_reports(&report, &["loopy", "can finish without giving a value"]);
+
+    let report = rejects(
+        "give_without_else",
+        "cmd half (n:int): int {\n  if (n > 0) {\n    give 1\n  }\n}\n",
+    );
+    assert_reports(&report, &["half", "can finish without giving a value"]);
+
+    let report = rejects(
+        "give_in_partial_match",
+        "cmd k (n:int): int {\n  match n {\n    1 => give 10\n  }\n}\n",
+    );
+    assert_reports(&report, &["`k`", "can finish without giving a value"]);
+}
+
+#[test]
+fn wait_too_long() {
+    let report = rejects("wait_too_long", "wait 2562047788015215hr\n");
+    assert_reports(&report, &["2562047788015215hr"]);
+
+    let report = rejects("wait_past_u64", "wait 99999999999999999999999ms\n");
+    assert_reports(
+        &report,
+        &["Invalid Time Value", "99999999999999999999999ms"],
+    );
+
+    let report = rejects("wait_no_amount", "wait ms\n");
+    assert_reports(&report, &["Invalid Time Value", "`ms`"]);
+    assert!(
+        !report.contains("assigned to the variable"),
+        "Nothing is assigned in a wait :~\n{}",
+        report
+    );
+}
+
+#[test]
+fn unbalanced_print_braces() {
+    let report = rejects("unclosed_brace", "may a = 1\nprintln {a\n");
+    assert_reports(&report, &["`{a` is never closed"]);
+
+    let report = rejects("stray_brace", "println a}\n");
+    assert_reports(&report, &["`}` closes no `{`"]);
+}
+
+#[test]
+fn bad_print_expression() {
+    let report = rejects("bad_number_in_print", "println {1e10}\n");
+    assert_reports(&report, &["`{1e10}` is not a valid expression"]);
+    assert!(
+        !report.contains("assigned to the variable"),
+        "Nothing is assigned in a print :~\n{}",
+        report
+    );
+
+    let report = rejects("big_number_in_print", "println a {1 + 99999999999} b\n");
+    assert_reports(
+        &report,
+        &["`{1 + 99999999999}` is not a valid expression, `99999999999` is not a value"],
+    );
+}
+
+#[test]
+fn type_mismatch() {
+    let report = rejects("assign_mismatch", "may a = 1\na = \"s\"\n");
+    assert_reports(
+        &report,
+        &["Line: 2", "`a` is of type int but `\"s\"` is of type str"],
+    );
+
+    let report = rejects(
+        "condition_mismatch",
+        "may a = 1\nwhile (a) {\n  a = a + 1\n}\n",
+    );
+    assert_reports(&report, &["Line: 2", "a condition must be a bool"]);
+
+    let report = rejects(
+        "give_mismatch",
+        "cmd f (s: str): int {\n  if (s == \"\") {\n    give 1\n  }\n  give s\n}\n",
+    );
+    assert_reports(&report, &["Line: 5", "the command's return type is int"]);
+
+    let report = rejects(
+        "argument_mismatch",
+        "cmd f (n: int) {\n  println {n}\n}\ncall f {\"x\"}\n",
+    );
+    assert_reports(
+        &report,
+        &["Line: 4", "The parameter `n` of `f` is of type int"],
+    );
+}
+
+#[test]
+fn unknown_type() {
+    let report = rejects("unknown_var_type", "may x: foo = 1\n");
+    assert_reports(
+        &report,
+        &["Line: 1", "`foo` is not a known type for the variable `x`"],
+    );
+
+    let report = rejects("unknown_return_type", "cmd f: foo {\n  give 1\n}\n");
+    assert_reports(
+        &report,
+        &["`foo` is not a known type for the return value of `f`"],
+    );
+
+    let report = rejects("missing_param_type", "cmd g (a: int, b) {\n}\n");
+    assert_reports(&report, &["No type is given for the parameter `b`"]);
+}
+
+#[test]
+fn dangling_else() {
+    let report = rejects("dangling_else", "may x = 1\nelse {\n  println e\n}\n");
+    assert_reports(&report, &["Line: 2", "`else` without a matching `if`"]);
+}
+
+#[test]
+fn code_after_a_statement() {
+    let report = rejects(
+        "second_statement",
+        "may x = 1\nif (x == 1) { x = 2 } println two\n",
+    );
+    assert_reports(
+        &report,
+        &["Line: 2", "`println two` does not start a statement"],
+    );
+
+    let report = rejects("stray_close", "may x = 1\n}\n");
+    assert_reports(&report, &["Line: 2", "`}` does not start a statement"]);
+}
+
+#[test]
+fn unknown_first_word() {
+    let report = rejects("misspelled_keyword", "may x = 1\nprnt hi\n");
+    assert_reports(
+        &report,
+        &["Line: 2", "`prnt hi` does not start a statement"],
+    );
+
+    let report = rejects("bare_name", "may x = 1\nx\n");
+    assert_reports(&report, &["Line: 2", "`x` does not start a statement"]);
+}
+
+#[test]
+fn break_outside_a_loop() {
+    let report = rejects("break_at_top", "break\n");
+    assert_reports(&report, &["Line: 1", "`break` is used outside of a loop"]);
+
+    // A command body does not count as inside a loop, even when called from one
+    let report = rejects(
+        "continue_in_cmd",
+        "cmd f {\n  continue\n}\nwhile (true) {\n  call f\n}\n",
+    );
+    assert_reports(
+        &report,
+        &["Line: 2", "`continue` is used outside of a loop"],
+    );
+}
+
+#[test]
+fn shape_fields() {
+    let report = rejects("unknown_field_type", "shape N {\n  next: N\n}\n");
+    assert_reports(
+        &report,
+        &["Line: 1", "`N` is not a known type for the field `next`"],
+    );
+    assert!(
+        !report.contains("has no fields"),
+        "A shape with a bad field is not empty :~\n{}",
+        report
+    );
+}
+
+#[test]
+fn empty_declarations() {
+    let report = rejects("empty_enum", "enum E { }\n");
+    assert_reports(&report, &["Line: 1", "an enum needs at least one variant"]);
+
+    let report = rejects("empty_shape", "shape S {\n}\n");
+    assert_reports(&report, &["Line: 1", "a shape needs at least one field"]);
+}
+
+#[test]
+fn enum_matches() {
+    let report = rejects(
+        "missing_variant",
+        "enum C { R, G, B }\nmay c = C.R\nmatch c {\n  C.R => println r\n  C.G => println g\n}\n",
+    );
+    assert_reports(&report, &["Line: 3", "does not cover `B`"]);
+
+    let report = rejects(
+        "duplicate_arm",
+        "enum C { R, G }\nmay c = C.R\nmatch c {\n  C.R => println r\n  C.R => println again\n  _ => println other\n}\n",
+    );
+    assert_reports(
+        &report,
+        &["Line: 5", "`C.R` is already covered by an earlier arm"],
+    );
+
+    let report = rejects(
+        "unknown_variant",
+        "enum C { R, G }\nmay c = C.R\nmatch c {\n  Blue => println b\n  _ => println other\n}\n",
+    );
+    assert_reports(&report, &["Line: 4", "The enum C has no variant `Blue`"]);
+}
+
+#[test]
+fn import_cycles() {
+    let report = rejects_files(
+        "import_cycle",
+        &[
+            ("main.nsc", "use a.nsc\n"),
+            ("a.nsc", "use b.nsc\n"),
+            ("b.nsc", "use a.nsc\ncmd x {\n  println x\n}\n"),
+        ],
+    );
+    assert_reports(
+        &report,
+        &["Importing `a.nsc` forms a cycle: `a.nsc` -> `b.nsc` -> `a.nsc`"],
+    );
+
+    let report = rejects_files("import_itself", &[("me.nsc", "use me.nsc\n")]);
+    assert_reports(
+        &report,
+        &["Importing `me.nsc` forms a cycle: `me.nsc` -> `me.nsc`"],
+    );
+}
+
+#[test]
+fn constants() {
+    let report = rejects("const_reassigned", "const A = 1\nA = 2\n");
+    assert_reports(
+        &report,
+        &["Line: 2", "`A` is a constant, so it cannot be changed"],
+    );
+
+    let report = rejects("const_div_zero", "const B = 1 / 0\n");
+    assert_reports(&report, &["`(1 / 0)` divides by zero"]);
+
+    let report = rejects("const_overflow", "const C = 2147483647 + 1\n");
+    assert_reports(&report, &["`(2147483647 + 1)` does not fit in an int"]);
+
+    let report = rejects("const_from_variable", "may v = 3\nconst D = v + 1\n");
+    assert_reports(
+        &report,
+        &[
+            "Line: 2",
+            "`v` is not made of literals, constants and operators",
+        ],
+    );
+}
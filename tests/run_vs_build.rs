@@ -0,0 +1,177 @@
+//! Runs every program in `tests/programs` through the interpreter (`neit run`) and as the binary
+//! `neit build` makes of it, and checks that both print the same output and exit the same way.
+//!
+//! A program reads `<name>.in` from stdin when there is one, and nothing otherwise. Only stdout
+//! and the exit code are compared, as the interpreter colors its error messages on stderr.
+//!
+//! Each program is also built with AddressSanitizer, whose LeakSanitizer reports any memory the
+//! generated C never frees.
+//!
+//! The tests need clang and fail without it, unless `NEIT_SKIP_CLANG` is set.
+
+use std::{
+    env, fs,
+    io::Write,
+    path::{Path, PathBuf},
+    process::{self, Command, Output, Stdio},
+};
+
+const NEIT: &str = env!("CARGO_BIN_EXE_neit");
+
+/// Runs `cmd` with `input` on its stdin and waits for it to finish.
+fn run_with_input(mut cmd: Command, input: &[u8]) -> Output {
+    let mut child = cmd
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap_or_else(|e| panic!("Unable to start {:?} :~ {}", cmd, e));
+    // A program that stops reading early closes the pipe, which is not a failure here
+    let _ = child.stdin.take().unwrap().write_all(input);
+    child.wait_with_output().unwrap()
+}
+
+/// Whether clang can be started. Without it the test fails, as a skip would pass for a check
+/// that never ran, unless `NEIT_SKIP_CLANG` is set to skip it on purpose.
+fn have_clang() -> bool {
+    if Command::new("clang").arg("--version").output().is_ok() {
+        return true;
+    }
+    assert!(
+        env::var_os("NEIT_SKIP_CLANG").is_some(),
+        "clang is not installed, so nothing can be built. Install it, or set NEIT_SKIP_CLANG=1 to skip the tests that need it"
+    );
+    eprintln!("clang is not installed and NEIT_SKIP_CLANG is set, skipping");
+    false
+}
+
+fn programs() -> Vec<PathBuf> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/programs");
+    let mut programs: Vec<PathBuf> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "nsc"))
+        .collect();
+    programs.sort();
+    programs
+}
+
+/// Builds `src` with `neit build` and `args`, which writes the binary, and with `-rc` the C, next
+/// to where it is started.
+fn build(src: &Path, out_dir: &Path, args: &[&str]) {
+    let name = src.file_stem().unwrap().to_string_lossy();
+    fs::create_dir_all(out_dir).unwrap();
+    let built = Command::new(NEIT)
+        .arg("build")
+        .arg(src)
+        .args(args)
+        .current_dir(out_dir)
+        .output()
+        .unwrap();
+    assert!(
+        built.status.success(),
+        "Building `{}` failed :~\n{}{}",
+        name,
+        String::from_utf8_lossy(&built.stdout),
+        String::from_utf8_lossy(&built.stderr)
+    );
+}
+
+#[test]
+fn run_and_build_agree() {
+    if !have_clang() {
+        return;
+    }
+
+    let work = env::temp_dir().join(format!("neit-run-vs-build-{}", process::id()));
+    let mut mismatches = Vec::new();
+    for src in programs() {
+        let name = src.file_stem().unwrap().to_string_lossy().into_owned();
+        let input = fs::read(src.with_extension("in")).unwrap_or_default();
+
+        let out_dir = work.join(&name);
+        build(&src, &out_dir, &[]);
+
+        let mut interpreter = Command::new(NEIT);
+        interpreter.arg("run").arg(&src);
+        let interpreted = run_with_input(interpreter, &input);
+        let binary = out_dir
+            .join("output")
+            .with_extension(env::consts::EXE_EXTENSION);
+        let compiled = run_with_input(Command::new(binary), &input);
+
+        if interpreted.stdout != compiled.stdout
+            || interpreted.status.code() != compiled.status.code()
+        {
+            mismatches.push(format!(
+                "`{}`\n--- run ({:?}) ---\n{}\n--- build ({:?}) ---\n{}",
+                name,
+                interpreted.status.code(),
+                String::from_utf8_lossy(&interpreted.stdout),
+                compiled.status.code(),
+                String::from_utf8_lossy(&compiled.stdout)
+            ));
+        }
+    }
+    let _ = fs::remove_dir_all(&work);
+
+    assert!(
+        mismatches.is_empty(),
+        "run and build disagree on {} program(s) :~\n\n{}",
+        mismatches.len(),
+        mismatches.join("\n\n")
+    );
+}
+
+// LeakSanitizer only comes with AddressSanitizer by default on Linux
+#[cfg(target_os = "linux")]
+#[test]
+fn builds_free_their_memory() {
+    if !have_clang() {
+        return;
+    }
+
+    let work = env::temp_dir().join(format!("neit-leaks-{}", process::id()));
+    let mut reports = Vec::new();
+    for src in programs() {
+        let name = src.file_stem().unwrap().to_string_lossy().into_owned();
+        let input = fs::read(src.with_extension("in")).unwrap_or_default();
+
+        let out_dir = work.join(&name);
+        build(&src, &out_dir, &["-rc"]);
+        let compiled = Command::new("clang")
+            .args([
+                "output.c",
+                "nulibc.c",
+                "-fwrapv",
+                "-g",
+                "-fsanitize=address",
+            ])
+            .args(["-o", "checked"])
+            .current_dir(&out_dir)
+            .output()
+            .unwrap();
+        assert!(
+            compiled.status.success(),
+            "Building `{}` with AddressSanitizer failed :~\n{}",
+            name,
+            String::from_utf8_lossy(&compiled.stderr)
+        );
+
+        let mut checked = Command::new(out_dir.join("checked"));
+        checked.env("ASAN_OPTIONS", "detect_leaks=1");
+        let ran = run_with_input(checked, &input);
+        let stderr = String::from_utf8_lossy(&ran.stderr);
+        if stderr.contains("ERROR: AddressSanitizer") || stderr.contains("ERROR: LeakSanitizer") {
+            reports.push(format!("`{}`\n{}", name, stderr));
+        }
+    }
+    let _ = fs::remove_dir_all(&work);
+
+    assert!(
+        reports.is_empty(),
+        "{} program(s) leak or misuse memory :~\n\n{}",
+        reports.len(),
+        reports.join("\n\n")
+    );
+}